@@ -0,0 +1,44 @@
+"""Generates the sound effects in this directory, which are synthesized rather than recorded.
+
+Run from this directory with `python3 generate.py`. The output is deterministic, so the committed
+files can be checked against it.
+"""
+import math
+import random
+import struct
+import wave
+
+RATE = 22050
+
+
+def write(name, samples):
+    with wave.open(name, 'wb') as w:
+        w.setnchannels(1)
+        w.setsampwidth(2)
+        w.setframerate(RATE)
+        w.writeframes(b''.join(struct.pack('<h', int(max(-1, min(1, s)) * 30000)) for s in samples))
+
+
+def tone(freq, dur, decay, vol=0.6, noise=0.0):
+    """A decaying sine wave, optionally mixed with white noise for a wooden click"""
+    random.seed(1)
+    n = int(RATE * dur)
+    return [vol * math.exp(-decay * i / RATE)
+            * ((1 - noise) * math.sin(2 * math.pi * freq * i / RATE) + noise * random.uniform(-1, 1))
+            for i in range(n)]
+
+
+def silence(dur):
+    return [0.0] * int(RATE * dur)
+
+
+# wooden click for a move
+write('move.wav', tone(520, 0.09, 45, 0.7, 0.35))
+# sharper, louder click for a capture
+write('capture.wav', [a + b for a, b in zip(tone(380, 0.14, 30, 0.6, 0.5), tone(760, 0.14, 40, 0.3))])
+# two clicks for castling
+write('castle.wav', tone(520, 0.07, 50, 0.6, 0.35) + silence(0.05) + tone(440, 0.09, 45, 0.6, 0.35))
+# rising two-tone chime for check
+write('check.wav', tone(880, 0.12, 12, 0.4) + tone(1175, 0.2, 10, 0.4))
+# descending three-tone chime for the end of the game
+write('game_over.wav', tone(784, 0.18, 6, 0.4) + tone(659, 0.18, 6, 0.4) + tone(523, 0.45, 4, 0.4))
@@ -1,10 +1,48 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use crate::network::inspect::FrameFormat;
+use crate::network::transport::{FrameMode, TransportKind};
 
 #[derive(Debug, Parser)]
-#[command(version)]
+#[command(version, args_conflicts_with_subcommands = true)]
 pub struct Cli {
     #[command(subcommand)]
-    pub command: Option<Commands>
+    pub command: Option<Commands>,
+
+    /// Options for local play, which is started when no subcommand is given
+    #[command(flatten)]
+    pub gui: GuiOptions,
+}
+
+/// Options for the game window, shared by all play modes
+#[derive(Clone, Debug, Args)]
+pub struct GuiOptions {
+    /// Hide the file and rank labels on the edge of the board, they can also be toggled with L
+    #[arg(long)]
+    pub no_coordinates: bool,
+
+    /// In local play, flip the board after every move so that the player to move is at the bottom
+    #[arg(long)]
+    pub auto_flip: bool,
+
+    /// Milliseconds that pieces take to slide to their new square after a move, 0 disables the
+    /// animations
+    #[arg(long, default_value_t = 200)]
+    pub animation_duration: u64,
+
+    /// Start with sound effects muted, they can also be toggled with M
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Print a text announcement of every move, selection and change of the game state to stdout,
+    /// for use with a screen reader
+    #[arg(long)]
+    pub announce: bool,
+
+    /// Speak the announcements with this command instead of printing them, such as `spd-say`. The
+    /// text is passed as the last argument. Implies --announce
+    #[arg(long, value_name = "COMMAND")]
+    pub speech_command: Option<String>,
 }
 
 #[derive(Clone, Debug, Subcommand)]
@@ -14,9 +52,11 @@ pub enum Commands {
         /// The local IPv4 address to bind the server to
         bind_address: String,
 
-        /// Enforce a strict rule policy and reject invalid moves from the opponent
-        #[arg(short, long)]
-        strict: bool,
+        #[command(flatten)]
+        options: ConnectionOptions,
+
+        #[command(flatten)]
+        gui: GuiOptions,
     },
 
     /// Join a game server, playing as white
@@ -24,8 +64,132 @@ pub enum Commands {
         /// The IPv4/IPv6 address of the server to join
         address: String,
 
-        /// If true, reject invalid moves from the opponent
+        #[command(flatten)]
+        options: ConnectionOptions,
+
+        #[command(flatten)]
+        gui: GuiOptions,
+    },
+
+    /// Check another client's protocol implementation by playing a scripted game against it
+    Conformance {
+        /// The address to bind the server to, or of the server to join
+        address: String,
+
+        /// Host the game server and play as black, instead of joining and playing as white
+        #[arg(long)]
+        host: bool,
+
+        #[command(flatten)]
+        options: ConnectionOptions,
+    },
+
+    /// Play a correspondence game, where moves are exchanged through a game file instead of a live
+    /// connection
+    Correspondence {
+        /// The game file, which is created if it does not exist
+        file: PathBuf,
+
+        /// Play as black instead of white
+        #[arg(long)]
+        black: bool,
+
+        /// Enforce a strict rule policy and reject invalid moves from the opponent
         #[arg(short, long)]
         strict: bool,
+
+        /// The directory to write a log of the session to
+        #[arg(long, default_value = "logs")]
+        log_dir: PathBuf,
+
+        #[command(flatten)]
+        gui: GuiOptions,
     },
+
+    /// Sit between a host and a joiner, forwarding and recording every frame
+    Proxy {
+        /// The address to accept the joining player on
+        listen_address: String,
+
+        /// The address of the hosting player's server
+        host_address: String,
+
+        /// The file to write the recording to
+        #[arg(short, long, default_value = "recording.txt")]
+        record: PathBuf,
+
+        #[command(flatten)]
+        options: ConnectionOptions,
+    },
+
+    /// Play back a recording made by `proxy` against a live client, acting as its opponent
+    Replay {
+        /// The recording to play back
+        recording: PathBuf,
+
+        /// The address to bind the server to, or of the server to join
+        address: String,
+
+        /// Replay the joining player by joining a live host, instead of replaying the host
+        #[arg(long)]
+        join: bool,
+
+        #[command(flatten)]
+        options: ConnectionOptions,
+    },
+
+    /// Decode and pretty-print captured chess protocol frames
+    Inspect {
+        /// The file to read frames from, or `-` to read from stdin
+        #[arg(default_value = "-")]
+        input: PathBuf,
+
+        /// How the frames are stored in the input
+        #[arg(short, long, value_enum, default_value_t = FrameFormat::Raw)]
+        format: FrameFormat,
+
+        /// The framing the frames were sent with
+        #[arg(long, value_enum, default_value_t = FrameMode::Fixed)]
+        frames: FrameMode,
+    },
+}
+
+/// Options shared by all remote play modes
+#[derive(Clone, Debug, Args)]
+pub struct ConnectionOptions {
+    /// The kind of connection to use
+    #[arg(short, long, value_enum, default_value_t = TransportKind::Tcp)]
+    pub transport: TransportKind,
+
+    /// Enforce a strict rule policy and reject invalid moves from the opponent
+    #[arg(short, long)]
+    pub strict: bool,
+
+    /// Only play against someone who knows this passphrase, and encrypt all messages with it
+    #[arg(short, long)]
+    pub passphrase: Option<String>,
+
+    /// The framing to use for messages. When joining, `variable` requests variable-length frames
+    /// from the host, and when hosting, it allows them
+    #[arg(short, long, value_enum, default_value_t = FrameMode::Fixed)]
+    pub frames: FrameMode,
+
+    /// Seconds of silence from the opponent before the connection is considered lost
+    #[arg(long, default_value_t = 30)]
+    pub timeout: u64,
+
+    /// Send heartbeats to measure the latency and detect when the opponent stops responding,
+    /// which requires a client that supports them. Heartbeats are also sent once the opponent
+    /// sends one
+    #[arg(long)]
+    pub heartbeat: bool,
+
+    /// Send your arrows and marked squares to the opponent, which requires a client that supports
+    /// annotation messages
+    #[arg(long)]
+    pub share_annotations: bool,
+
+    /// The directory to write a log of the session to
+    #[arg(long, default_value = "logs")]
+    pub log_dir: PathBuf,
 }
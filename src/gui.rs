@@ -1,24 +1,66 @@
 use std::error::Error;
+use std::time::Duration;
 
 use ggez::event;
 use ggez::graphics;
-use ggez::input::keyboard::KeyInput;
-use rsoderh_chess::{Color, FinishedGame, Game, GameResult, HalfMoveRequest, MoveResult, PieceKind, Position};
+use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
+use rsoderh_chess::{Board, Color, FinishedGame, Game, GameResult, HalfMoveRequest, MoveResult, PieceKind, Position, Slot};
+use crate::args::GuiOptions;
 use crate::network::chess_tp::GameStateType;
-use crate::network::chess_tp::Message;
-use crate::resources::Resources;
+use crate::network::chess_tp::{clone_move, Annotation, AnnotationColor, Message};
+use crate::resources::{Resources, SoundEffect};
 use drawing::colors::*;
-use crate::network::GameConnection;
+use animation::MoveAnimation;
+use announcer::{AnnouncedState, Announcer};
+use drawing::KingHighlight;
+use crate::network::{GameConnection, ReadError};
+use crate::network::transport::TransportError;
 use crate::util::ReplaceCell;
 
+mod animation;
+mod announcer;
 mod drawing;
+mod notation;
 mod util;
 
+const CONNECTION_LOST_TEXT: &str =
+    "Connection lost: press W to keep waiting, C to claim the win or Q to quit";
+
+fn result_text(result: GameStateType) -> &'static str {
+    match result {
+        GameStateType::Normal => "Game in progress",
+        GameStateType::WhiteWon => "White won",
+        GameStateType::BlackWon => "Black won",
+        GameStateType::Draw => "Draw",
+    }
+}
+
 /// Contains the current state of a chess game, whether it is ongoing or finished
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum GameState {
     OngoingGame(Game),
     FinishedGame(FinishedGame),
+    AdjudicatedGame(AdjudicatedGame),
+}
+
+/// A game whose result was decided outside of the rules of chess, such as by the opponent's
+/// connection being lost
+#[derive(Debug)]
+struct AdjudicatedGame {
+    board: Board,
+    result: GameStateType,
+    description: String,
+}
+
+/// The opponent's version of the game, after it has diverged from ours
+#[derive(Debug)]
+struct Desync {
+    board: Board,
+    result: GameStateType,
+    description: String,
+    /// Whether the opponent's board is currently displayed instead of ours
+    viewing: bool,
 }
 
 /// Represents a selected square and a cache of its available moves
@@ -42,10 +84,44 @@ pub struct GuiState {
     hovered_square: Option<Position>,
     selected_square: Option<SquareSelection>,
     promotion_selection: Option<Position>,
+    /// Source and destination squares of the latest move
+    last_move: Option<(Position, Position)>,
+    /// Source and destination squares of moves queued during the opponent's turn, which are
+    /// performed in order as soon as it is our turn
+    premoves: Vec<(Position, Position)>,
+    animation: Option<MoveAnimation>,
+    /// Sound effect to play on the next update
+    pending_sound: Option<SoundEffect>,
+    /// Arrows and marked squares drawn by the local player
+    annotations: Vec<Annotation>,
+    /// Arrows and marked squares shared by the other player
+    remote_annotations: Vec<Annotation>,
+    /// Square where the right mouse button was pressed, to start an annotation
+    annotation_start: Option<Position>,
+    /// Text typed into the move input box, if it is open
+    move_input: Option<String>,
+    /// Why the last move typed into the move input box was rejected
+    move_input_error: Option<String>,
+    /// Whether the hovered square was last moved with the keyboard rather than the mouse
+    keyboard_cursor: bool,
+    announcer: Option<Announcer>,
+    /// State of the game when it was last announced
+    announced_state: Option<AnnouncedState>,
+    notice: Option<String>,
+    connection_lost: bool,
+    desync: Option<Desync>,
+
+    // settings
+    show_coordinates: bool,
+    /// Whether the player has flipped the board, relative to its default orientation
+    board_flipped: bool,
+    auto_flip: bool,
+    animation_duration: Duration,
+    muted: bool,
 }
 
 impl GuiState {
-    pub fn new_local(ctx: &mut ggez::Context) -> ggez::GameResult<GuiState> {
+    pub fn new_local(ctx: &mut ggez::Context, options: &GuiOptions) -> ggez::GameResult<GuiState> {
         Ok(GuiState {
             resources: Resources::new(ctx)?,
             game_state: ReplaceCell::new(GameState::OngoingGame(Game::new_standard())),
@@ -53,10 +129,33 @@ impl GuiState {
             hovered_square: None,
             selected_square: None,
             promotion_selection: None,
+            last_move: None,
+            premoves: Vec::new(),
+            animation: None,
+            pending_sound: None,
+            annotations: Vec::new(),
+            remote_annotations: Vec::new(),
+            annotation_start: None,
+            move_input: None,
+            move_input_error: None,
+            keyboard_cursor: false,
+            announcer: (options.announce || options.speech_command.is_some())
+                .then(|| Announcer::new(options.speech_command.as_deref())),
+            announced_state: None,
+            notice: None,
+            connection_lost: false,
+            desync: None,
+            show_coordinates: !options.no_coordinates,
+            board_flipped: false,
+            auto_flip: options.auto_flip,
+            animation_duration: Duration::from_millis(options.animation_duration),
+            muted: options.mute,
         })
     }
 
-    pub fn new_remote(ctx: &mut ggez::Context, connection: GameConnection) -> Result<GuiState, Box<dyn Error>> {
+    pub fn new_remote(ctx: &mut ggez::Context, connection: GameConnection,
+                      options: &GuiOptions) -> Result<GuiState, Box<dyn Error>>
+    {
         Ok(GuiState {
             resources: Resources::new(ctx)?,
             game_state: ReplaceCell::new(GameState::OngoingGame(Game::new_standard())),
@@ -64,25 +163,69 @@ impl GuiState {
             hovered_square: None,
             selected_square: None,
             promotion_selection: None,
+            last_move: None,
+            premoves: Vec::new(),
+            animation: None,
+            pending_sound: None,
+            annotations: Vec::new(),
+            remote_annotations: Vec::new(),
+            annotation_start: None,
+            move_input: None,
+            move_input_error: None,
+            keyboard_cursor: false,
+            announcer: (options.announce || options.speech_command.is_some())
+                .then(|| Announcer::new(options.speech_command.as_deref())),
+            announced_state: None,
+            notice: None,
+            connection_lost: false,
+            desync: None,
+            show_coordinates: !options.no_coordinates,
+            board_flipped: false,
+            auto_flip: options.auto_flip,
+            animation_duration: Duration::from_millis(options.animation_duration),
+            muted: options.mute,
         })
     }
 
+    /// Replay moves made before the game was opened, such as those of a resumed correspondence
+    /// game, without sending them to the other player
+    pub fn replay_moves(&mut self, moves: Vec<HalfMoveRequest>) {
+        for chess_move in moves {
+            self.try_move(chess_move, true);
+        }
+        self.animation = None;
+        self.pending_sound = None;
+    }
+
     pub fn is_local_player_turn(&self) -> bool {
         match self.game_state.get_ref() {
             GameState::OngoingGame(game) => {
                 self.connection.as_ref()
                     .is_none_or(|connection| connection.local_player() == game.turn)
             },
-            GameState::FinishedGame(finished_game) => false,
+            GameState::FinishedGame(_) | GameState::AdjudicatedGame(_) => false,
         }
     }
 
+    fn is_remote_player_turn(&self) -> bool {
+        self.ongoing().is_some_and(|game| {
+            self.connection.as_ref()
+                .is_some_and(|connection| connection.local_player() != game.turn)
+        })
+    }
+
     pub fn on_quit(&mut self, message: Option<String>) {
         if let Some(connection) = &mut self.connection {
             let _ = connection.quit(message);
         }
     }
 
+    fn log_event(&mut self, text: &str) {
+        if let Some(connection) = &mut self.connection {
+            connection.log_event(text);
+        }
+    }
+
     fn reset_selection(&mut self) {
         self.selected_square = None;
         self.hovered_square = None;
@@ -91,33 +234,23 @@ impl GuiState {
 
     fn game_state_type(&self) -> GameStateType {
         match self.game_state.get_ref() {
-            GameState::OngoingGame(game) => GameStateType::Normal,
+            GameState::OngoingGame(_) => GameStateType::Normal,
             GameState::FinishedGame(game) => match game.result() {
-                GameResult::Checkmate { winner, attacked_king } => {
+                GameResult::Checkmate { winner, attacked_king: _ } => {
                     match winner {
                         Color::White => GameStateType::WhiteWon,
                         Color::Black => GameStateType::BlackWon,
                     }
                 },
-            }
+            },
+            GameState::AdjudicatedGame(game) => game.result,
         }
     }
 
     fn try_move(&mut self, chess_move: HalfMoveRequest, is_remote_move: bool) {
-        fn clone_chess_move(chess_move: &HalfMoveRequest) -> HalfMoveRequest {
-            match chess_move {
-                HalfMoveRequest::Standard { source, dest } => {
-                    HalfMoveRequest::Standard { source: *source, dest: *dest }
-                },
-                HalfMoveRequest::Promotion { column, kind } => {
-                    HalfMoveRequest::Promotion { column: *column, kind: *kind }
-                },
-            }
-        }
-
         fn get_game_state_type(game_result: &GameResult) -> GameStateType {
             match game_result {
-                GameResult::Checkmate { winner, attacked_king } => {
+                GameResult::Checkmate { winner, attacked_king: _ } => {
                     match winner {
                         Color::White => GameStateType::WhiteWon,
                         Color::Black => GameStateType::BlackWon,
@@ -129,82 +262,209 @@ impl GuiState {
         self.game_state.replace(|game_state| match game_state {
             GameState::OngoingGame(game) => {
                 let player = game.turn;
+                let move_squares = util::move_squares(&game, &chess_move);
+                let move_sound = match move_squares {
+                    Some((source, dest)) if util::is_castling(game.board(), source, dest) => {
+                        SoundEffect::Castle
+                    },
+                    Some((source, dest)) if util::is_capture(game.board(), source, dest) => {
+                        SoundEffect::Capture
+                    },
+                    _ => SoundEffect::Move,
+                };
+                let animation = move_squares
+                    .filter(|_| !self.animation_duration.is_zero())
+                    .and_then(|(source, dest)| {
+                        MoveAnimation::new(game.board(), source, dest, self.animation_duration)
+                    });
                 let (new_game_state,
                     new_game_state_type,
                     new_board
-                ) = match game.perform_move(clone_chess_move(&chess_move)) {
+                ) = match game.perform_move(clone_move(&chess_move)) {
                     MoveResult::Ongoing(game, ..) => {
                         let new_board = game.board().clone();
+                        let is_check = util::checked_king(&new_board, game.turn).is_some();
+                        self.pending_sound =
+                            Some(if is_check { SoundEffect::Check } else { move_sound });
                         (GameState::OngoingGame(game), GameStateType::Normal, new_board)
                     },
                     MoveResult::Finished(game) => {
                         let game_state_type = get_game_state_type(game.result());
                         let new_board = game.board().clone();
+                        self.pending_sound = Some(SoundEffect::GameOver);
                         (GameState::FinishedGame(game), game_state_type, new_board)
                     },
                     MoveResult::Illegal(game, _) => {
                         return GameState::OngoingGame(game);
                     }
                 };
-                if !is_remote_move {
-                    if let Some(connection) = &mut self.connection {
-                        let _ = connection.send_message(Message::ChessMove {
-                            player: Some(player),
-                            chess_move,
-                            new_game_state: new_game_state_type,
-                            new_board,
-                        });
-                    }
+                self.last_move = move_squares;
+                self.animation = animation;
+                if is_remote_move {
+                    // a selection made for a premove has no available moves to choose from
+                    self.selected_square = None;
+                    self.promotion_selection = None;
+                }
+                // annotations are about the previous position, so both players clear them
+                self.annotations.clear();
+                self.remote_annotations.clear();
+                if !is_remote_move && let Some(connection) = &mut self.connection {
+                    let _ = connection.send_message(Message::ChessMove {
+                        player: Some(player),
+                        chess_move,
+                        new_game_state: new_game_state_type,
+                        new_board,
+                    });
                 }
                 new_game_state
             }
-            GameState::FinishedGame(game) => GameState::FinishedGame(game),
+            game_state => game_state,
         });
     }
 
+    /// Handle every message that has been received from the other player, regardless of whose
+    /// turn it is
+    fn dispatch_messages(&mut self, ctx: &mut ggez::Context) {
+        loop {
+            let Some(connection) = &mut self.connection else { return; };
+            match connection.read_message() {
+                Ok(Some(message)) => self.handle_message(ctx, message),
+                Ok(None) => return,
+                Err(ReadError::Transport(TransportError::Unauthenticated)) => {
+                    self.notice = Some(
+                        "Rejected a message that failed authentication".to_owned());
+                },
+                Err(_) => {},
+            }
+        }
+    }
+
+    /// Handle a message that breaks the rules of the game, by quitting under a strict rule policy
+    /// and ignoring it otherwise
+    fn reject_message(&mut self, ctx: &mut ggez::Context, reason: &str) {
+        println!("{reason}");
+        self.log_event(&format!("Rejected message: {reason}"));
+        if self.connection.as_ref().is_some_and(|conn| conn.strict_rule_policy()) {
+            self.on_quit(Some(reason.to_owned()));
+            ctx.request_quit();
+        } else {
+            self.notice = Some(format!("Ignored message: {reason}"));
+        }
+    }
+
     fn handle_message(&mut self, ctx: &mut ggez::Context, message: Message) {
         match message {
             Message::ChessMove {
-                player,
+                player: _,
                 chess_move,
                 new_game_state,
                 new_board
             } => {
+                if self.desync.is_some() {
+                    self.reject_message(ctx, "Move received while positions diverged");
+                    return;
+                }
+                if !self.is_remote_player_turn() {
+                    self.reject_message(ctx, "Other player moved out of turn");
+                    return;
+                }
                 self.try_move(chess_move, true);
 
-                let board = match self.game_state.get_ref() {
-                    GameState::OngoingGame(game) => game.board(),
-                    GameState::FinishedGame(finished_game) => finished_game.board(),
-                };
+                let board = self.board();
                 let game_state = self.game_state_type();
                 if new_board != *board || new_game_state != game_state {
                     if self.connection.as_ref().is_some_and(|conn| conn.strict_rule_policy()) {
                         println!("Other player made an invalid move");
+                        self.log_event("Other player made an invalid move");
                         self.on_quit(Some("Invalid move".to_owned()));
                         ctx.request_quit();
                     } else {
-                        self.game_state.replace(|game_state| {
-                            let turn = if let GameState::OngoingGame(game) = game_state {
-                                game.turn
-                            } else {
-                                return game_state;
-                            };
-                            match new_game_state {
-                                GameStateType::Normal => {
-                                    GameState::OngoingGame(Game::new(new_board, turn))
-                                },
-                                GameStateType::WhiteWon | GameStateType::BlackWon | GameStateType::Draw => {
-                                    unimplemented!("Cannot construct `FinishedGame`")
-                                },
-                            }
+                        let description = if new_game_state != game_state {
+                            format!("opponent reports \"{}\", we have \"{}\"",
+                                    result_text(new_game_state), result_text(game_state))
+                        } else {
+                            "opponent's board differs from ours".to_owned()
+                        };
+                        self.log_event(&format!("Desync: {description}"));
+                        self.reset_selection();
+                        self.desync = Some(Desync {
+                            board: new_board,
+                            result: new_game_state,
+                            description,
+                            viewing: false,
                         });
                     }
                 }
             },
             Message::ChessQuit { payload } => {
                 println!("Other player quit: {payload}");
+                self.log_event(&format!("Other player quit: {payload}"));
                 ctx.request_quit();
             },
+            Message::ChessAnnotate { annotations } => {
+                self.remote_annotations = annotations;
+            },
+            // heartbeats are answered by the connection itself
+            Message::ChessPing { .. } | Message::ChessPong { .. } => {},
+        }
+    }
+
+    /// Replace our game with the opponent's version after a desync. Finished games are recorded
+    /// as adjudicated, since a [FinishedGame] cannot be constructed from a board and a result.
+    fn accept_desync(&mut self) {
+        let Some(desync) = self.desync.take() else { return; };
+        let Some(turn) = self.connection.as_ref().map(|conn| conn.local_player()) else { return; };
+        self.log_event(&format!("Accepted opponent's version: {}", result_text(desync.result)));
+        // nothing shown for the abandoned position applies to the opponent's version
+        self.animation = None;
+        self.last_move = None;
+        self.premoves.clear();
+        self.reset_selection();
+        self.annotations.clear();
+        self.remote_annotations.clear();
+        self.game_state.replace(|_| match desync.result {
+            GameStateType::Normal => GameState::OngoingGame(Game::new(desync.board, turn)),
+            result => GameState::AdjudicatedGame(AdjudicatedGame {
+                board: desync.board,
+                result,
+                description: format!("{} (as reported by the opponent)", result_text(result)),
+            }),
+        });
+    }
+
+    /// Abort the game after a desync, without accepting the opponent's version
+    fn abort_desync(&mut self, ctx: &mut ggez::Context) {
+        self.desync = None;
+        self.on_quit(Some("Game aborted after the players' positions diverged".to_owned()));
+        ctx.request_quit();
+    }
+
+    /// End the game in favor of the local player, after the opponent's connection was lost
+    fn claim_win(&mut self) {
+        let Some(winner) = self.connection.as_ref().map(|conn| conn.local_player()) else { return; };
+        self.log_event("Claimed the win after the connection was lost");
+        self.game_state.replace(|game_state| match game_state {
+            GameState::OngoingGame(game) => GameState::AdjudicatedGame(AdjudicatedGame {
+                board: game.board().clone(),
+                result: match winner {
+                    Color::White => GameStateType::WhiteWon,
+                    Color::Black => GameStateType::BlackWon,
+                },
+                description: "You won, the opponent's connection was lost".to_owned(),
+            }),
+            game_state => game_state,
+        });
+        self.connection_lost = false;
+        self.reset_selection();
+        self.pending_sound = Some(SoundEffect::GameOver);
+        self.on_quit(Some("Opponent's connection was lost".to_owned()));
+    }
+
+    fn board(&self) -> &Board {
+        match self.game_state.get_ref() {
+            GameState::OngoingGame(game) => game.board(),
+            GameState::FinishedGame(finished_game) => finished_game.board(),
+            GameState::AdjudicatedGame(game) => &game.board,
         }
     }
 
@@ -220,11 +480,43 @@ impl GuiState {
         }
     }
 
+    /// returns: The square of the king that is in check or checkmated, if any
+    fn attacked_king(&self) -> Option<(Position, KingHighlight)> {
+        match self.game_state.get_ref() {
+            GameState::OngoingGame(game) => util::checked_king(game.board(), game.turn)
+                .map(|king| (king, KingHighlight::Check)),
+            GameState::FinishedGame(finished_game) => match finished_game.result() {
+                GameResult::Checkmate { attacked_king, .. } =>
+                    Some((*attacked_king, KingHighlight::Checkmate)),
+            },
+            GameState::AdjudicatedGame(_) => None,
+        }
+    }
+
+    /// returns: Whether the board is displayed with black at the bottom. Black is at the bottom
+    ///          for a remote black player, or for the player to move in local play with auto-flip
+    ///          enabled, unless the player has flipped the board.
     fn render_flipped(&self) -> bool {
-        self.connection.as_ref().is_some_and(|conn| matches!(conn.local_player(), Color::Black))
+        let default_flipped = match &self.connection {
+            Some(connection) => matches!(connection.local_player(), Color::Black),
+            None => {
+                self.auto_flip && self.ongoing().is_some_and(|game| matches!(game.turn, Color::Black))
+            },
+        };
+        default_flipped != self.board_flipped
+    }
+
+    /// Update the hovered square to the square under the mouse cursor, such as after the board's
+    /// orientation has changed
+    fn update_hovered_square(&mut self, ctx: &ggez::Context) {
+        if self.is_ongoing() {
+            let mouse_pos = ctx.mouse.position();
+            self.hovered_square =
+                util::global_to_board_pos(ctx, (mouse_pos.x, mouse_pos.y), self.render_flipped());
+        }
     }
 
-    fn status_text(&self) -> &str {
+    fn status_text(&self) -> String {
         let (white_to_play, white_won, black_to_play, black_won) = if let Some(connection) = &self.connection {
             let (your_turn, you_won, opponents_turn, opponent_won) = (
                 "Your turn", "You won by checkmate!", "Opponent's turn", "Opponent won by checkmate!",
@@ -237,16 +529,190 @@ impl GuiState {
             ("White to play", "White won by checkmate!", "Black to play", "Black won by checkmate!")
         };
         match self.game_state.get_ref() {
-            GameState::OngoingGame(game) => match game.turn {
-                Color::White => white_to_play,
-                Color::Black => black_to_play,
-            }
+            GameState::OngoingGame(game) => {
+                let to_play = match game.turn {
+                    Color::White => white_to_play,
+                    Color::Black => black_to_play,
+                };
+                if util::checked_king(game.board(), game.turn).is_some() {
+                    format!("{to_play} - Check!")
+                } else {
+                    to_play.to_owned()
+                }
+            },
             GameState::FinishedGame(finished_game) => match finished_game.result() {
                 GameResult::Checkmate { winner, .. } => match winner {
                     Color::White => white_won,
                     Color::Black => black_won,
-                },
+                }.to_owned(),
+            },
+            GameState::AdjudicatedGame(game) => game.description.clone(),
+        }
+    }
+
+    /// returns: The notice shown underneath the status text, such as the keys to press when the
+    ///          connection is lost
+    fn notice_text(&self) -> Option<String> {
+        if self.connection_lost {
+            Some(CONNECTION_LOST_TEXT.to_owned())
+        } else if let Some(desync) = &self.desync {
+            let viewed = if desync.viewing { "theirs" } else { "ours" };
+            Some(format!(
+                "Desync, {}: press A to accept their version, X to abort or V to compare \
+                 (viewing {viewed})", desync.description))
+        } else {
+            self.notice.clone()
+        }
+    }
+
+    fn connection_text(&self) -> Option<String> {
+        let connection = self.connection.as_ref()?;
+        if !self.is_ongoing() { return None; }
+        if connection.is_resumable() {
+            return Some(if self.is_remote_player_turn() {
+                "Move saved, waiting for the opponent's move".to_owned()
+            } else {
+                "Correspondence game".to_owned()
+            });
+        }
+        Some(match connection.latency() {
+            Some(latency) => format!("Latency: {} ms", latency.as_millis()),
+            None => "Latency: unknown".to_owned(),
+        })
+    }
+
+    /// Add an annotation, or remove it if the same annotation has already been drawn
+    fn toggle_annotation(&mut self, annotation: Annotation) {
+        let existing = self.annotations.iter().position(|a| *a == annotation);
+        match existing {
+            Some(index) => { self.annotations.remove(index); },
+            None => {
+                // an annotation of a different color on the same squares is replaced
+                self.annotations.retain(|a| !same_squares(a, &annotation));
+                self.annotations.push(annotation);
+            },
+        }
+        self.send_annotations();
+    }
+
+    fn clear_annotations(&mut self) {
+        if self.annotations.is_empty() { return; }
+        self.annotations.clear();
+        self.send_annotations();
+    }
+
+    fn send_annotations(&mut self) {
+        let Some(connection) = &mut self.connection else { return; };
+        if connection.shares_annotations() {
+            let annotations = self.annotations.clone();
+            if connection.send_message(Message::ChessAnnotate { annotations }).is_err() {
+                self.notice = Some("Too many annotations to share with the opponent".to_owned());
+            }
+        }
+    }
+
+    /// returns: The parts of the current state that are announced when they change
+    fn announced_state(&self) -> AnnouncedState {
+        AnnouncedState {
+            board: self.board().clone(),
+            last_move: self.last_move,
+            selection: self.selected_square.as_ref()
+                .map(|selection| (selection.pos, selection.available_moves.to_vec())),
+            promotion_selection: self.promotion_selection,
+            premoves: self.premoves.clone(),
+            cursor: self.hovered_square.filter(|_| self.keyboard_cursor),
+            status: self.status_text(),
+            notice: self.notice_text(),
+            move_input_error: self.move_input_error.clone(),
+        }
+    }
+
+    /// Announce everything that changed since the previous announcement
+    fn announce_changes(&mut self) {
+        if self.announcer.is_none() { return; }
+        let state = self.announced_state();
+        let text = match &self.announced_state {
+            Some(announced) => announcer::describe_changes(announced, &state),
+            None => Some(state.status.clone()),
+        };
+        self.announced_state = Some(state);
+        if let Some(text) = text {
+            self.announce(&text);
+        }
+    }
+
+    fn announce(&mut self, text: &str) {
+        let Some(announcer) = &mut self.announcer else { return; };
+        if let Err(err) = announcer.announce(text) {
+            // fall back to the console, rather than silently losing the announcements
+            eprintln!("Failed to run the speech command: {err}");
+            *announcer = Announcer::new(None);
+            let _ = announcer.announce(text);
+        }
+    }
+
+    /// Move the keyboard cursor, which is shown as the hovered square, by the given number of
+    /// columns and rows as seen on screen
+    fn move_cursor(&mut self, right: i8, up: i8) {
+        if !self.is_ongoing() { return; }
+        self.keyboard_cursor = true;
+        let (right, up) = if self.render_flipped() { (-right, -up) } else { (right, up) };
+        let Some(cursor) = self.hovered_square else {
+            // start on the square in front of the king of the player at the bottom
+            let row = if self.render_flipped() { 6 } else { 1 };
+            self.hovered_square = Position::new(4, row);
+            return;
+        };
+        let column = (cursor.column.get() as i8 + right).clamp(0, 7);
+        let row = (cursor.row.get() as i8 + up).clamp(0, 7);
+        self.hovered_square = Position::new(column as u8, row as u8);
+    }
+
+    /// Click the square under the keyboard cursor, keeping the cursor where it is
+    fn cursor_click(&mut self) {
+        let Some(cursor) = self.hovered_square else { return; };
+        self.handle_board_click(Some(cursor));
+        if self.is_ongoing() {
+            self.hovered_square = Some(cursor);
+        }
+    }
+
+    /// Choose a piece from the promotion selection menu, by its index in the menu
+    fn choose_promotion(&mut self, index: usize) {
+        let (Some(game), Some(promotion_square)) = (self.ongoing(), self.promotion_selection)
+        else {
+            return;
+        };
+        let Some((square, _)) = util::promotion_selection_iter(game.turn, promotion_square).nth(index)
+        else {
+            return;
+        };
+        let cursor = self.hovered_square;
+        self.handle_promotion_selection_click(square);
+        if self.is_ongoing() {
+            self.hovered_square = cursor;
+        }
+    }
+
+    /// Perform the move typed into the move input box, closing it if the move is valid
+    fn submit_move_input(&mut self) {
+        let Some(input) = self.move_input.as_deref() else { return; };
+        let Some(game) = self.ongoing() else {
+            self.move_input_error = Some("The game is over".to_owned());
+            return;
+        };
+        if !self.is_local_player_turn() || self.desync.is_some() {
+            self.move_input_error = Some("It is not your turn".to_owned());
+            return;
+        }
+        match notation::parse_move(game, input) {
+            Ok(chess_move) => {
+                self.reset_selection();
+                self.try_move(chess_move, false);
+                self.move_input = None;
+                self.move_input_error = None;
             },
+            Err(error) => self.move_input_error = Some(error),
         }
     }
 
@@ -271,9 +737,84 @@ impl GuiState {
         self.reset_selection();
     }
 
+    /// Select a piece, or queue a premove of the selected piece, during the opponent's turn.
+    /// Whether the premove is legal is only known once the opponent has moved.
+    fn handle_premove_click(&mut self, clicked_square: Option<Position>) {
+        let Some(clicked_square) = clicked_square else {
+            self.reset_selection();
+            return;
+        };
+        let Some(player) = self.connection.as_ref().map(|connection| connection.local_player())
+        else {
+            return;
+        };
+        let is_own_piece = self.premove_board().at_position(clicked_square).as_piece()
+            .is_some_and(|piece| piece.color == player);
+        match self.selected_square.as_ref().map(|selection| selection.pos) {
+            Some(selected_square) if selected_square == clicked_square => self.reset_selection(),
+            Some(selected_square) if !is_own_piece => {
+                self.premoves.push((selected_square, clicked_square));
+                self.reset_selection();
+            },
+            _ if is_own_piece => {
+                self.selected_square = Some(SquareSelection {
+                    pos: clicked_square,
+                    available_moves: Box::new([]),
+                });
+            },
+            _ => {},
+        }
+    }
+
+    /// returns: The board as it will be after the queued premoves, ignoring the opponent's moves
+    fn premove_board(&self) -> Board {
+        let mut board = self.board().clone();
+        for (source, dest) in &self.premoves {
+            let Some(mut piece) = board.at_position(*source).as_piece().copied() else { continue; };
+            let is_last_rank = dest.row.get() == 0 || dest.row.get() == 7;
+            if matches!(piece.kind, PieceKind::Pawn) && is_last_rank {
+                piece.kind = PieceKind::Queen;
+            }
+            *board.at_position_mut(*source) = Slot::Empty;
+            *board.at_position_mut(*dest) = Slot::Occupied(piece);
+        }
+        board
+    }
+
+    /// Perform the first queued premove if it is our turn and the opponent's move has finished
+    /// animating, cancelling every premove if it is not legal. Pawns that are premoved to the last
+    /// rank are promoted to a queen.
+    fn perform_premove(&mut self) {
+        if self.premoves.is_empty() { return; }
+        if !self.is_ongoing() || self.desync.is_some() {
+            self.premoves.clear();
+            return;
+        }
+        if !self.is_local_player_turn() || self.animation.is_some() { return; }
+
+        let (source, dest) = self.premoves.remove(0);
+        let Some(game) = self.ongoing() else { return; };
+        let is_promotion = game.board().at_position(source).as_piece()
+            .is_some_and(|piece| matches!(piece.kind, PieceKind::Pawn))
+            && (dest.row.get() == 0 || dest.row.get() == 7);
+        let text = format!("{}{}", notation::square_name(source), notation::square_name(dest));
+        match notation::validate_move(game, source, dest,
+                                      is_promotion.then_some(PieceKind::Queen), &text) {
+            Ok(chess_move) => self.try_move(chess_move, false),
+            Err(err) => {
+                self.premoves.clear();
+                self.notice = Some(format!("Premove cancelled: {err}"));
+            },
+        }
+    }
+
     fn handle_board_click(&mut self, clicked_square: Option<Position>) {
+        if self.is_remote_player_turn() && self.desync.is_none() {
+            self.handle_premove_click(clicked_square);
+            return;
+        }
         let Some(game) = self.ongoing() else { return; };
-        if !self.is_local_player_turn() {
+        if !self.is_local_player_turn() || self.desync.is_some() {
             self.reset_selection();
             return;
         }
@@ -287,7 +828,7 @@ impl GuiState {
             return;
         };
 
-        if matches!(self.promotion_selection, Some(_)) {
+        if self.promotion_selection.is_some() {
             self.handle_promotion_selection_click(clicked_square);
             return;
         }
@@ -340,32 +881,101 @@ impl GuiState {
     }
 }
 
+/// returns: Whether both annotations mark the same square or connect the same squares
+fn same_squares(a: &Annotation, b: &Annotation) -> bool {
+    match (a, b) {
+        (Annotation::Mark { square: a, .. }, Annotation::Mark { square: b, .. }) => a == b,
+        (Annotation::Arrow { source: a_source, dest: a_dest, .. },
+            Annotation::Arrow { source: b_source, dest: b_dest, .. }) => {
+            a_source == b_source && a_dest == b_dest
+        },
+        _ => false,
+    }
+}
+
+/// returns: The annotation color chosen by the held modifier keys
+fn annotation_color(mods: KeyMods) -> AnnotationColor {
+    if mods.contains(KeyMods::SHIFT) {
+        AnnotationColor::Red
+    } else if mods.contains(KeyMods::CTRL) {
+        AnnotationColor::Blue
+    } else if mods.contains(KeyMods::ALT) {
+        AnnotationColor::Yellow
+    } else {
+        AnnotationColor::Green
+    }
+}
+
 impl event::EventHandler for GuiState {
     fn update(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
-        if !self.is_local_player_turn() {
-            if let Some(connection) = &mut self.connection {
-                if let Some(message) = connection.read_message().ok() {
-                    self.handle_message(ctx, message);
-                }
+        let is_ongoing = self.is_ongoing();
+        if let Some(connection) = &mut self.connection {
+            connection.poll();
+            let connection_lost = is_ongoing && connection.is_connection_lost();
+            if connection_lost && !self.connection_lost {
+                connection.log_event("Connection lost");
             }
+            self.connection_lost = connection_lost;
         }
+        self.dispatch_messages(ctx);
+        if let Some(animation) = &mut self.animation {
+            animation.advance(ctx.time.delta());
+            if animation.is_finished() {
+                self.animation = None;
+            }
+        }
+        if let Some(effect) = self.pending_sound.take() && !self.muted {
+            // a missing audio device should not interrupt the game
+            let _ = self.resources.sounds.play(ctx, effect);
+        }
+        self.announce_changes();
+        // after the sound and announcement of the opponent's move, so that the premove does not
+        // replace them
+        self.perform_premove();
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut ggez::Context) -> ggez::GameResult {
         let mut canvas = graphics::Canvas::from_frame(ctx, BACKGROUND_COLOR);
 
-        let board = match self.game_state.get_ref() {
-            GameState::OngoingGame(game) => game.board(),
-            GameState::FinishedGame(finished_game) => finished_game.board(),
+        let (board, animation) = match &self.desync {
+            Some(desync) if desync.viewing => (&desync.board, None),
+            _ => (self.board(), self.animation.as_ref()),
         };
 
         drawing::draw_board(ctx, &mut canvas, &self.resources.images, board,
                             self.selected_square.as_ref(), self.hovered_square,
                             self.ongoing().map(|game| game.turn),
-                            self.promotion_selection, self.render_flipped())?;;
+                            self.promotion_selection, self.last_move, &self.premoves,
+                            self.attacked_king(),
+                            animation, self.show_coordinates, self.render_flipped())?;
+        let (top_player, bottom_player) = if self.render_flipped() {
+            (Color::White, Color::Black)
+        } else {
+            (Color::Black, Color::White)
+        };
+        for (player, at_top) in [(top_player, true), (bottom_player, false)] {
+            let opponent = util::opponent(player);
+            let captured = util::captured_pieces(board, opponent);
+            let advantage = util::material(board, player)
+                .saturating_sub(util::material(board, opponent));
+            drawing::draw_captured_pieces(ctx, &mut canvas, &self.resources.images, &captured,
+                                          advantage, at_top)?;
+        }
+        drawing::draw_annotations(ctx, &mut canvas,
+                                  self.remote_annotations.iter().chain(self.annotations.iter()),
+                                  self.render_flipped())?;
 
-        drawing::draw_status_text(ctx, &mut canvas, self.status_text())?;
+        drawing::draw_status_text(ctx, &mut canvas, &self.status_text())?;
+        if let Some(notice) = self.notice_text() {
+            drawing::draw_notice_text(ctx, &mut canvas, &notice)?;
+        }
+        if let Some(input) = &self.move_input {
+            drawing::draw_move_input(ctx, &mut canvas, input, self.move_input_error.as_deref())?;
+        }
+        if let Some(connection_text) = self.connection_text() {
+            drawing::draw_connection_text(ctx, &mut canvas, &connection_text)?;
+        }
 
         canvas.finish(ctx)
     }
@@ -373,33 +983,137 @@ impl event::EventHandler for GuiState {
     fn mouse_button_down_event(&mut self, ctx: &mut ggez::Context, button: event::MouseButton,
                                x: f32, y: f32) -> ggez::GameResult
     {
+        if matches!(button, event::MouseButton::Right) {
+            if !self.premoves.is_empty() {
+                // cancelling the premoves takes the place of drawing an annotation
+                self.premoves.clear();
+                self.reset_selection();
+                return Ok(());
+            }
+            self.annotation_start = util::global_to_board_pos(ctx, (x, y), self.render_flipped());
+        }
         if matches!(button, event::MouseButton::Left) {
-            if let Some(game) = self.ongoing() {
+            self.clear_annotations();
+            if self.ongoing().is_some() {
                 let clicked_square = util::global_to_board_pos(ctx, (x, y), self.render_flipped());
                 self.handle_board_click(clicked_square);
+                if self.auto_flip {
+                    self.update_hovered_square(ctx);
+                }
             }
         }
         Ok(())
     }
 
+    fn mouse_button_up_event(&mut self, ctx: &mut ggez::Context, button: event::MouseButton,
+                             x: f32, y: f32) -> ggez::GameResult
+    {
+        if matches!(button, event::MouseButton::Right) {
+            let Some(start) = self.annotation_start.take() else { return Ok(()); };
+            let Some(end) = util::global_to_board_pos(ctx, (x, y), self.render_flipped()) else {
+                return Ok(());
+            };
+            let color = annotation_color(ctx.keyboard.active_mods());
+            self.toggle_annotation(if start == end {
+                Annotation::Mark { square: start, color }
+            } else {
+                Annotation::Arrow { source: start, dest: end, color }
+            });
+        }
+        Ok(())
+    }
+
     fn mouse_motion_event(&mut self, ctx: &mut ggez::Context, x: f32, y: f32,
                           _dx: f32, _dy: f32) -> ggez::GameResult
     {
         if self.is_ongoing() {
             self.hovered_square = util::global_to_board_pos(ctx, (x, y), self.render_flipped());
+            self.keyboard_cursor = false;
         }
         Ok(())
     }
 
-    fn key_down_event(&mut self, _ctx: &mut ggez::Context, _input: KeyInput,
+    fn key_down_event(&mut self, ctx: &mut ggez::Context, input: KeyInput,
                       _repeated: bool) -> ggez::GameResult
     {
         // overrides the default behavior of exiting the program when pressing ESC
+        if self.connection_lost {
+            match input.keycode {
+                Some(KeyCode::W) => {
+                    if let Some(connection) = &mut self.connection {
+                        connection.log_event("Waiting for the connection to recover");
+                        connection.keep_waiting();
+                    }
+                },
+                Some(KeyCode::C) => self.claim_win(),
+                Some(KeyCode::Q) => ctx.request_quit(),
+                _ => {},
+            }
+        } else if let Some(desync) = &mut self.desync {
+            match input.keycode {
+                Some(KeyCode::A) => self.accept_desync(),
+                Some(KeyCode::X) => self.abort_desync(ctx),
+                Some(KeyCode::V) => desync.viewing = !desync.viewing,
+                _ => {},
+            }
+        } else if let Some(move_input) = &mut self.move_input {
+            // characters are added by text_input_event
+            match input.keycode {
+                Some(KeyCode::Return | KeyCode::NumpadEnter) => self.submit_move_input(),
+                Some(KeyCode::Escape) => {
+                    self.move_input = None;
+                    self.move_input_error = None;
+                },
+                Some(KeyCode::Back) => { move_input.pop(); },
+                _ => {},
+            }
+        } else {
+            match input.keycode {
+                Some(KeyCode::Tab) => self.move_input = Some(String::new()),
+                Some(KeyCode::Up) => self.move_cursor(0, 1),
+                Some(KeyCode::Down) => self.move_cursor(0, -1),
+                Some(KeyCode::Left) => self.move_cursor(-1, 0),
+                Some(KeyCode::Right) => self.move_cursor(1, 0),
+                Some(KeyCode::Return | KeyCode::NumpadEnter | KeyCode::Space) => self.cursor_click(),
+                Some(KeyCode::Escape) => {
+                    self.selected_square = None;
+                    self.promotion_selection = None;
+                    self.premoves.clear();
+                },
+                Some(KeyCode::Key1) => self.choose_promotion(0),
+                Some(KeyCode::Key2) => self.choose_promotion(1),
+                Some(KeyCode::Key3) => self.choose_promotion(2),
+                Some(KeyCode::Key4) => self.choose_promotion(3),
+                Some(KeyCode::D) => self.announce(&announcer::describe_position(self.board())),
+                Some(KeyCode::L) => self.show_coordinates = !self.show_coordinates,
+                Some(KeyCode::M) => self.muted = !self.muted,
+                Some(KeyCode::F) => {
+                    self.board_flipped = !self.board_flipped;
+                    self.update_hovered_square(ctx);
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut ggez::Context,
+                        character: char) -> ggez::GameResult
+    {
+        if let Some(move_input) = &mut self.move_input && !character.is_control() {
+            move_input.push(character);
+        }
         Ok(())
     }
 
     fn quit_event(&mut self, _ctx: &mut ggez::Context) -> Result<bool, ggez::GameError> {
-        self.on_quit(Some("Player quit the game".into()));
+        let is_ongoing = self.is_ongoing();
+        match &mut self.connection {
+            Some(connection) if connection.is_resumable() && is_ongoing => {
+                let _ = connection.close();
+            },
+            _ => self.on_quit(Some("Player quit the game".into())),
+        }
         Ok(false)
     }
 }
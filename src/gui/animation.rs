@@ -0,0 +1,132 @@
+use std::time::Duration;
+use rsoderh_chess::{Board, Piece, PieceKind, Position};
+
+/// A piece sliding from one square to another
+#[derive(Copy, Clone, Debug)]
+struct MovingPiece {
+    piece: Piece,
+    source: Position,
+    dest: Position,
+}
+
+/// Animation of a move that has just been performed, with the moved pieces sliding to their new
+/// squares and any captured piece fading out
+#[derive(Clone, Debug)]
+pub struct MoveAnimation {
+    moving_pieces: Vec<MovingPiece>,
+    captured: Option<(Position, Piece)>,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl MoveAnimation {
+    /// # Arguments
+    ///
+    /// * `board`: The board before the move was performed
+    /// * `source`: The square of the moved piece
+    /// * `dest`: The destination of the moved piece
+    /// * `duration`: How long the animation lasts
+    ///
+    /// returns: The animation, or `None` if there is no piece at `source`
+    pub fn new(board: &Board, source: Position, dest: Position,
+               duration: Duration) -> Option<MoveAnimation>
+    {
+        let piece = *board.at_position(source).as_piece()?;
+        let mut moving_pieces = vec![MovingPiece { piece, source, dest }];
+        let mut captured = board.at_position(dest).as_piece().map(|captured| (dest, *captured));
+
+        let column_distance = dest.column.get() as i8 - source.column.get() as i8;
+        match piece.kind {
+            PieceKind::King if column_distance.abs() == 2 => {
+                // castling, the rook jumps to the square the king passed over
+                let rook_column = if column_distance > 0 { 7 } else { 0 };
+                let rook_source = Position::new(rook_column, source.row.get());
+                let rook_dest = Position::new(
+                    (source.column.get() + dest.column.get()) / 2, source.row.get());
+                if let (Some(rook_source), Some(rook_dest)) = (rook_source, rook_dest)
+                    && let Some(rook) = board.at_position(rook_source).as_piece()
+                {
+                    moving_pieces.push(
+                        MovingPiece { piece: *rook, source: rook_source, dest: rook_dest });
+                }
+            },
+            PieceKind::Pawn if column_distance != 0 && captured.is_none() => {
+                // en passant, the captured pawn is beside the source square
+                captured = Position::new(dest.column.get(), source.row.get())
+                    .and_then(|square| board.at_position(square).as_piece().map(|pawn| (square, *pawn)));
+            },
+            _ => {},
+        }
+
+        Some(MoveAnimation { moving_pieces, captured, elapsed: Duration::ZERO, duration })
+    }
+
+    /// Advance the animation by the time since the previous frame
+    pub fn advance(&mut self, delta: Duration) {
+        self.elapsed = self.elapsed.saturating_add(delta);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// returns: How far the animation has progressed, from 0 to 1, eased out so that pieces slow
+    ///          down as they approach their destination
+    fn progress(&self) -> f32 {
+        if self.duration.is_zero() { return 1_f32; }
+        let linear = (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1_f32);
+        1_f32 - (1_f32 - linear).powi(3)
+    }
+
+    /// returns: The piece sliding towards `square`, and its current offset from `square` in
+    ///          columns and rows
+    pub fn moving_piece_at(&self, square: Position) -> Option<(Piece, (f32, f32))> {
+        let remaining = 1_f32 - self.progress();
+        self.moving_pieces.iter()
+            .find(|moving| moving.dest == square)
+            .map(|moving| {
+                let columns = moving.source.column.get() as f32 - moving.dest.column.get() as f32;
+                let rows = moving.source.row.get() as f32 - moving.dest.row.get() as f32;
+                (moving.piece, (columns * remaining, rows * remaining))
+            })
+    }
+
+    /// returns: The captured piece fading out at `square`, and its current opacity
+    pub fn fading_piece_at(&self, square: Position) -> Option<(Piece, f32)> {
+        self.captured
+            .filter(|(captured_square, _)| *captured_square == square)
+            .map(|(_, piece)| (piece, 1_f32 - self.progress()))
+    }
+
+    /// returns: Whether `square` is the source of a moving piece, which should no longer be drawn
+    ///          there
+    pub fn is_vacated(&self, square: Position) -> bool {
+        self.moving_pieces.iter().any(|moving| moving.source == square)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsoderh_chess::Color;
+    use crate::gui::util::test_util::{board_with, square};
+    use super::*;
+
+    #[test]
+    fn castling_moves_the_rook() {
+        let board = board_with(&[("e1", Color::White, PieceKind::King), ("h1", Color::White, PieceKind::Rook)]);
+        let animation = MoveAnimation::new(&board, square("e1"), square("g1"), Duration::from_millis(100)).unwrap();
+        assert_eq!(animation.moving_piece_at(square("f1")).map(|(_, offset)| offset), Some((2_f32, 0_f32)));
+        assert!(animation.is_vacated(square("h1")));
+        assert!(animation.fading_piece_at(square("g1")).is_none());
+    }
+
+    #[test]
+    fn en_passant_fades_the_captured_pawn() {
+        let board = board_with(&[("e5", Color::White, PieceKind::Pawn), ("d5", Color::Black, PieceKind::Pawn)]);
+        let mut animation = MoveAnimation::new(&board, square("e5"), square("d6"), Duration::from_millis(100)).unwrap();
+        assert_eq!(animation.fading_piece_at(square("d5")).map(|(_, alpha)| alpha), Some(1_f32));
+        animation.advance(Duration::from_millis(100));
+        assert!(animation.is_finished());
+        assert_eq!(animation.moving_piece_at(square("d6")).map(|(_, offset)| offset), Some((0_f32, 0_f32)));
+    }
+}
@@ -0,0 +1,267 @@
+use std::io;
+use std::process::{Child, Command};
+use rsoderh_chess::{Board, Color, Piece, PieceKind, Position};
+use crate::gui::notation::square_name;
+use crate::gui::util;
+
+/// Sends text announcements of the game to an accessibility channel, so that the game can be
+/// followed with a screen reader
+#[derive(Debug)]
+pub struct Announcer {
+    /// Program and arguments that the announcement text is appended to, such as `spd-say`. The
+    /// announcements are printed to stdout if there is none.
+    speech_command: Option<Vec<String>>,
+    /// Speech processes that may still be running
+    children: Vec<Child>,
+}
+
+impl Announcer {
+    /// # Arguments
+    ///
+    /// * `speech_command`: Command that speaks its last argument, split on whitespace, or `None`
+    ///   to print the announcements to stdout
+    pub fn new(speech_command: Option<&str>) -> Announcer {
+        Announcer {
+            speech_command: speech_command
+                .map(|command| command.split_whitespace().map(str::to_owned).collect())
+                .filter(|command: &Vec<String>| !command.is_empty()),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn announce(&mut self, text: &str) -> io::Result<()> {
+        // reap the processes of earlier announcements that have finished
+        self.children.retain_mut(|child| child.try_wait().is_ok_and(|status| status.is_none()));
+        let Some((program, args)) = self.speech_command.as_ref()
+            .and_then(|command| command.split_first())
+        else {
+            println!("{text}");
+            return Ok(());
+        };
+        self.children.push(Command::new(program).args(args).arg(text).spawn()?);
+        Ok(())
+    }
+}
+
+/// The parts of the gui state that are announced when they change
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnnouncedState {
+    pub board: Board,
+    pub last_move: Option<(Position, Position)>,
+    /// Selected square and the squares its piece can move to
+    pub selection: Option<(Position, Vec<Position>)>,
+    pub promotion_selection: Option<Position>,
+    /// Source and destination squares of the queued premoves
+    pub premoves: Vec<(Position, Position)>,
+    /// Square of the keyboard cursor, which is not announced when it follows the mouse
+    pub cursor: Option<Position>,
+    pub status: String,
+    /// Notice shown underneath the status text
+    pub notice: Option<String>,
+    /// Why the move typed into the move input box was rejected
+    pub move_input_error: Option<String>,
+}
+
+/// returns: Description of what changed between two states, or `None` if nothing worth
+///          announcing changed
+pub fn describe_changes(before: &AnnouncedState, after: &AnnouncedState) -> Option<String> {
+    let mut sentences = Vec::new();
+    if after.last_move != before.last_move && let Some((source, dest)) = after.last_move {
+        sentences.push(describe_move(&before.board, &after.board, source, dest));
+    }
+    if after.status != before.status {
+        sentences.push(after.status.clone());
+    }
+    for (before, after) in [(&before.notice, &after.notice),
+                            (&before.move_input_error, &after.move_input_error)]
+    {
+        if let Some(text) = after.as_ref().filter(|_| after != before) {
+            sentences.push(text.clone());
+        }
+    }
+    if after.cursor != before.cursor && let Some(cursor) = after.cursor {
+        sentences.push(describe_square(&after.board, cursor));
+    }
+    if after.selection != before.selection {
+        match &after.selection {
+            Some((square, moves)) => {
+                sentences.push(describe_selection(&after.board, *square, moves));
+            },
+            // a move also clears the selection, which does not need to be announced separately
+            None if after.last_move == before.last_move => {
+                sentences.push("Selection cleared".to_owned());
+            },
+            None => {},
+        }
+    }
+    if after.premoves.len() > before.premoves.len() {
+        if let Some((source, dest)) = after.premoves.last() {
+            sentences.push(
+                format!("Premove {} to {} queued", square_name(*source), square_name(*dest)));
+        }
+    } else if after.premoves.is_empty() && !before.premoves.is_empty()
+        && after.last_move == before.last_move
+    {
+        sentences.push("Premoves cancelled".to_owned());
+    }
+    let promotion_changed = after.promotion_selection != before.promotion_selection;
+    if promotion_changed && after.promotion_selection.is_some() {
+        sentences.push("Choose a promotion: 1 queen, 2 knight, 3 rook, 4 bishop".to_owned());
+    }
+    (!sentences.is_empty()).then(|| sentences.join(". "))
+}
+
+/// returns: Description of the move of the piece at `source` to `dest`
+///
+/// # Arguments
+///
+/// * `before`: The board before the move
+/// * `after`: The board after the move
+fn describe_move(before: &Board, after: &Board, source: Position, dest: Position) -> String {
+    let Some(piece) = before.at_position(source).as_piece().copied() else {
+        return format!("{} to {}", square_name(source), square_name(dest));
+    };
+    if util::is_castling(before, source, dest) {
+        let side = if dest.column.get() > source.column.get() { "kingside" } else { "queenside" };
+        return format!("{} castles {side}", color_name(piece.color));
+    }
+    let mut text = format!("{} {} {} to {}", color_name(piece.color), kind_name(piece.kind),
+                           square_name(source), square_name(dest));
+    if util::is_capture(before, source, dest) {
+        let captured = before.at_position(dest).as_piece().copied()
+            .map(piece_name)
+            .unwrap_or_else(|| {
+                format!("{} pawn en passant", color_name(util::opponent(piece.color)).to_lowercase())
+            });
+        text.push_str(&format!(", takes {captured}"));
+    }
+    if let Some(promoted) = after.at_position(dest).as_piece()
+        .filter(|promoted| !util::same_kind(promoted.kind, piece.kind))
+    {
+        text.push_str(&format!(", promotes to {}", kind_name(promoted.kind)));
+    }
+    text
+}
+
+fn describe_selection(board: &Board, square: Position, moves: &[Position]) -> String {
+    let selected = describe_square(board, square);
+    // the moves of a piece selected for a premove are not known until the opponent has moved
+    if moves.is_empty() {
+        return format!("Selected {selected}");
+    }
+    let moves: Vec<String> = moves.iter().map(|dest| square_name(*dest)).collect();
+    format!("Selected {selected}, moves to {}", moves.join(", "))
+}
+
+/// returns: The name of `square` and the piece on it, such as `e4 white pawn` or `e5 empty`
+fn describe_square(board: &Board, square: Position) -> String {
+    match board.at_position(square).as_piece() {
+        Some(piece) => format!("{} {}", square_name(square), piece_name(*piece)),
+        None => format!("{} empty", square_name(square)),
+    }
+}
+
+/// returns: Every piece on the board listed by square, grouped by color and type
+pub fn describe_position(board: &Board) -> String {
+    const KINDS: [PieceKind; 6] = [
+        PieceKind::King, PieceKind::Queen, PieceKind::Rook,
+        PieceKind::Bishop, PieceKind::Knight, PieceKind::Pawn,
+    ];
+    let sides = [Color::White, Color::Black].map(|color| {
+        let groups: Vec<String> = KINDS.iter()
+            .filter_map(|kind| {
+                let squares: Vec<String> = util::all_squares()
+                    .filter(|square| board.at_position(*square).as_piece().is_some_and(|piece| {
+                        piece.color == color && util::same_kind(piece.kind, *kind)
+                    }))
+                    .map(square_name)
+                    .collect();
+                match squares.len() {
+                    0 => None,
+                    1 => Some(format!("{} {}", kind_name(*kind), squares[0])),
+                    _ => Some(format!("{}s {}", kind_name(*kind), squares.join(", "))),
+                }
+            })
+            .collect();
+        format!("{}: {}", color_name(color), groups.join("; "))
+    });
+    sides.join(". ")
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "White",
+        Color::Black => "Black",
+    }
+}
+
+fn kind_name(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Pawn => "pawn",
+        PieceKind::Knight => "knight",
+        PieceKind::Bishop => "bishop",
+        PieceKind::Rook => "rook",
+        PieceKind::Queen => "queen",
+        PieceKind::King => "king",
+    }
+}
+
+fn piece_name(piece: Piece) -> String {
+    format!("{} {}", color_name(piece.color).to_lowercase(), kind_name(piece.kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gui::util::test_util::{board_with, square};
+    use super::*;
+
+    fn state(board: Board) -> AnnouncedState {
+        AnnouncedState {
+            board,
+            last_move: None,
+            selection: None,
+            promotion_selection: None,
+            premoves: Vec::new(),
+            cursor: None,
+            status: "White to play".to_owned(),
+            notice: None,
+            move_input_error: None,
+        }
+    }
+
+    #[test]
+    fn describes_position_by_square() {
+        let board = board_with(&[
+            ("e1", Color::White, PieceKind::King), ("a2", Color::White, PieceKind::Pawn),
+            ("b2", Color::White, PieceKind::Pawn), ("e8", Color::Black, PieceKind::King),
+        ]);
+        assert_eq!(describe_position(&board), "White: king e1; pawns a2, b2. Black: king e8");
+    }
+
+    #[test]
+    fn describes_moves_and_selections() {
+        let before = state(board_with(&[
+            ("e1", Color::White, PieceKind::King), ("d4", Color::White, PieceKind::Knight),
+            ("e6", Color::Black, PieceKind::Pawn), ("e8", Color::Black, PieceKind::King),
+        ]));
+        let selected = AnnouncedState {
+            selection: Some((square("d4"), vec![square("e6"), square("f5")])),
+            ..before.clone()
+        };
+        assert_eq!(describe_changes(&before, &selected).as_deref(),
+                   Some("Selected d4 white knight, moves to e6, f5"));
+        assert_eq!(describe_changes(&selected, &selected), None);
+
+        let moved = AnnouncedState {
+            board: board_with(&[
+                ("e1", Color::White, PieceKind::King), ("e6", Color::White, PieceKind::Knight),
+                ("e8", Color::Black, PieceKind::King),
+            ]),
+            last_move: Some((square("d4"), square("e6"))),
+            status: "Black to play".to_owned(),
+            ..before.clone()
+        };
+        assert_eq!(describe_changes(&selected, &moved).as_deref(),
+                   Some("White knight d4 to e6, takes black pawn. Black to play"));
+    }
+}
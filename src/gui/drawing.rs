@@ -4,14 +4,35 @@ pub mod colors;
 use ggez::graphics;
 use ggez::graphics::PxScale;
 use rsoderh_chess::{Color, Piece, Position};
-use colors::BOARD_BORDER_COLOR;
+use colors::{BOARD_BORDER_COLOR, CONNECTION_TEXT_COLOR, MATERIAL_TEXT_COLOR, MOVE_INPUT_BACKGROUND_COLOR,
+             MOVE_INPUT_TEXT_COLOR, NOTICE_TEXT_COLOR};
 use crate::gui;
+use crate::gui::animation::MoveAnimation;
 use crate::gui::SquareSelection;
+use crate::network::chess_tp::Annotation;
 use crate::resources::ImageResources;
 
 const SQUARE_BORDER_THICKNESS: f32 = 0.05;
 const TARGET_CIRCLE_RADIUS: f32 = 0.4;
 const TARGET_CIRCLE_THICKNESS: f32 = 0.05;
+const KING_GLOW_RADIUS: f32 = 0.5;
+const KING_GLOW_LAYERS: u8 = 6;
+const MARK_RADIUS: f32 = 0.45;
+const MARK_THICKNESS: f32 = 0.08;
+const ARROW_WIDTH: f32 = 0.18;
+const ARROW_HEAD_LENGTH: f32 = 0.4;
+const ARROW_HEAD_WIDTH: f32 = 0.5;
+/// Distance between the tip of an arrow and the center of its destination square
+const ARROW_TIP_INSET: f32 = 0.1;
+/// Size of the captured pieces shown beside the board, relative to the square size
+const CAPTURED_PIECE_SIZE: f32 = 0.35;
+const CAPTURED_PIECES_PER_ROW: usize = 5;
+/// Distance between the left edge of the captured pieces and the center of the board
+const CAPTURED_PIECES_X: f32 = 4.4;
+/// Size of the file and rank labels, relative to the square size
+const COORDINATE_TEXT_SIZE: f32 = 0.2;
+/// Distance between the coordinate labels and the edge of their square
+const COORDINATE_TEXT_MARGIN: f32 = 0.05;
 
 #[derive(Copy, Clone, Debug)]
 pub enum TextAlignHorizontal {
@@ -39,6 +60,17 @@ enum SquareDrawColor {
     Selected,
     /// Displays options for performing a promotion move
     PromotionSelection,
+    /// Source or destination of the latest move
+    LastMove,
+    /// Source or destination of a queued premove
+    Premove,
+}
+
+/// Highlight of a king that is under attack
+#[derive(Copy, Clone, Debug)]
+pub enum KingHighlight {
+    Check,
+    Checkmate,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -46,33 +78,58 @@ struct SquareDrawState {
     hovered: bool,
     color: SquareDrawColor,
     piece: Option<Piece>,
+    king_highlight: Option<KingHighlight>,
+    /// Offset of the piece from the square in columns and rows, while it is moving to the square
+    piece_offset: Option<(f32, f32)>,
+    /// Captured piece that is fading out, along with its opacity
+    fading_piece: Option<(Piece, f32)>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn get_square_render_state(square: Position, piece: Option<Piece>,
                            selected_square: Option<&SquareSelection>,
                            hovered_square: Option<Position>,
                            turn: Option<Color>,
-                           promotion_selection: Option<Position>) -> SquareDrawState
+                           promotion_selection: Option<Position>,
+                           last_move: Option<(Position, Position)>,
+                           premoves: &[(Position, Position)],
+                           attacked_king: Option<(Position, KingHighlight)>) -> SquareDrawState
 {
     let hovered = hovered_square.is_some_and(|s| s == square);
+    let king_highlight = attacked_king
+        .and_then(|(king, highlight)| (king == square).then_some(highlight));
+    let is_last_move = last_move.is_some_and(|(source, dest)| source == square || dest == square);
+    let is_premove = premoves.iter().any(|(source, dest)| *source == square || *dest == square);
+    let unselected_color = if is_premove {
+        SquareDrawColor::Premove
+    } else if is_last_move {
+        SquareDrawColor::LastMove
+    } else {
+        SquareDrawColor::Normal
+    };
 
     let Some(turn) = turn else {
         return SquareDrawState {
             hovered,
-            color: SquareDrawColor::Normal,
+            color: unselected_color,
             piece,
+            king_highlight,
+            piece_offset: None,
+            fading_piece: None,
         };
     };
 
     if let Some(promotion_type) = promotion_selection
-        .map(|promotion_square|
+        .and_then(|promotion_square|
         gui::util::promotion_selection_type(turn, promotion_square, square))
-        .flatten()
     {
         return SquareDrawState {
             hovered,
             color: SquareDrawColor::PromotionSelection,
             piece: Some(Piece { kind: promotion_type, color: turn }),
+            king_highlight: None,
+            piece_offset: None,
+            fading_piece: None,
         };
     }
 
@@ -82,6 +139,9 @@ fn get_square_render_state(square: Position, piece: Option<Piece>,
             hovered,
             color: SquareDrawColor::Selected,
             piece,
+            king_highlight,
+            piece_offset: None,
+            fading_piece: None,
         };
     }
 
@@ -89,19 +149,42 @@ fn get_square_render_state(square: Position, piece: Option<Piece>,
         .is_some_and(|sel|
         sel.available_moves.contains(&square));
 
-    return SquareDrawState {
+    SquareDrawState {
         hovered,
-        color: if is_targeted { SquareDrawColor::Targeted } else { SquareDrawColor::Normal },
+        color: if is_targeted { SquareDrawColor::Targeted } else { unselected_color },
         piece,
-    };
+        king_highlight,
+        piece_offset: None,
+        fading_piece: None,
+    }
+}
+
+/// Replace the pieces of a square with those of the move animation, if it involves the square
+fn apply_move_animation(draw_state: &mut SquareDrawState, square: Position,
+                        animation: &MoveAnimation)
+{
+    if matches!(draw_state.color, SquareDrawColor::PromotionSelection) { return; }
+    if animation.is_vacated(square) {
+        draw_state.piece = None;
+    }
+    if let Some((piece, offset)) = animation.moving_piece_at(square) {
+        draw_state.piece = Some(piece);
+        draw_state.piece_offset = Some(offset);
+    }
+    draw_state.fading_piece = animation.fading_piece_at(square);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_board(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
                   resources: &ImageResources, board: &rsoderh_chess::Board,
                   selected_square: Option<&SquareSelection>,
                   hovered_square: Option<Position>, turn: Option<Color>,
                   promotion_selection: Option<Position>,
-                  render_flipped: bool) -> ggez::GameResult
+                  last_move: Option<(Position, Position)>,
+                  premoves: &[(Position, Position)],
+                  attacked_king: Option<(Position, KingHighlight)>,
+                  animation: Option<&MoveAnimation>,
+                  show_coordinates: bool, render_flipped: bool) -> ggez::GameResult
 {
     let square_params = util::square_draw_param(ctx);
     // board border
@@ -109,6 +192,7 @@ pub fn draw_board(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
         ctx, canvas, square_params, BOARD_BORDER_COLOR, -4.15_f32, -4.15_f32, 8.3_f32, 8.3_f32,
     )?;
     // board squares
+    let mut moving_pieces = Vec::new();
     for i in 0..8_u8 {
         for j in 0..8_u8 {
             let index = if render_flipped {
@@ -117,26 +201,69 @@ pub fn draw_board(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
                 (i, j)
             };
             let pos = Position::from_pair(index).unwrap();
-            let piece = board.at_position(pos).as_piece().map(|p| *p);
-            let square_render_state = get_square_render_state(
-                pos, piece, selected_square, hovered_square, turn, promotion_selection);
+            let piece = board.at_position(pos).as_piece().copied();
+            let mut square_render_state = get_square_render_state(
+                pos, piece, selected_square, hovered_square, turn, promotion_selection, last_move,
+                premoves, attacked_king);
+            if let Some(animation) = animation {
+                apply_move_animation(&mut square_render_state, pos, animation);
+            }
             draw_board_square(ctx, canvas, square_params, resources, index,
                               square_render_state, render_flipped)?;
+            if show_coordinates {
+                draw_coordinate_labels(ctx, canvas, index, render_flipped)?;
+            }
+            if square_render_state.piece_offset.is_some() {
+                moving_pieces.push((index, square_render_state));
+            }
         }
     }
+    // moving pieces are drawn last, so that squares they pass over are not drawn on top of them
+    for (index, square_render_state) in moving_pieces {
+        draw_moving_piece(ctx, canvas, resources, index, square_render_state, render_flipped);
+    }
     Ok(())
 }
 
+/// returns: The top left corner of the square at the given index, in board coordinate space
+fn square_board_pos(index: (u8, u8), render_flipped: bool) -> (f32, f32) {
+    if render_flipped {
+        (3_f32 - index.0 as f32, index.1 as f32 - 4_f32)
+    } else {
+        (index.0 as f32 - 4_f32, 3_f32 - index.1 as f32)
+    }
+}
+
+fn draw_piece(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas, resources: &ImageResources,
+              piece: Piece, (x, y): (f32, f32), opacity: f32)
+{
+    let image = &resources.get_piece(piece).resource;
+    let image_size = (image.width() as f32, image.height() as f32);
+    let image_params = util::board_relative_draw_param(
+        ctx, image_size, (x + 0.5_f32, y + 0.5_f32), 0.9_f32
+    );
+    canvas.draw(image, image_params.color(graphics::Color::new(1_f32, 1_f32, 1_f32, opacity)));
+}
+
+/// Draw the piece moving to the square at the given index, at its current position
+fn draw_moving_piece(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
+                     resources: &ImageResources, index: (u8, u8), draw_state: SquareDrawState,
+                     render_flipped: bool)
+{
+    let (Some(piece), Some((columns, rows))) = (draw_state.piece, draw_state.piece_offset) else {
+        return;
+    };
+    let (x, y) = square_board_pos(index, render_flipped);
+    let offset = if render_flipped { (-columns, rows) } else { (columns, -rows) };
+    draw_piece(ctx, canvas, resources, piece, (x + offset.0, y + offset.1), 1_f32);
+}
+
 fn draw_board_square(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
                      square_params: graphics::DrawParam, resources: &ImageResources,
                      index: (u8, u8), draw_state: SquareDrawState,
                      render_flipped: bool) -> ggez::GameResult
 {
-    let (x, y) = if render_flipped {
-        (3_f32 - index.0 as f32, index.1 as f32 - 4_f32)
-    } else {
-        (index.0 as f32 - 4_f32, 3_f32 - index.1 as f32)
-    };
+    let (x, y) = square_board_pos(index, render_flipped);
     let is_dark_square = util::is_dark_square(index);
 
     let (square_color, border_color) = colors::square_colors(is_dark_square, draw_state.color);
@@ -169,14 +296,148 @@ fn draw_board_square(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
         )?;
     }
 
-    if let Some(piece) = draw_state.piece {
-        let image = &resources.get_piece(piece).resource;
+    if let Some(king_highlight) = draw_state.king_highlight {
+        // radial glow, drawn as stacked translucent circles that get smaller towards the center
+        let glow_color = colors::king_glow_color(king_highlight);
+        for layer in 0..KING_GLOW_LAYERS {
+            let radius = KING_GLOW_RADIUS * (1_f32 - layer as f32 / KING_GLOW_LAYERS as f32);
+            util::draw_circle(
+                ctx, canvas, square_params, glow_color,
+                x + 0.5_f32, y + 0.5_f32, radius,
+            )?;
+        }
+    }
+
+    if let Some((piece, opacity)) = draw_state.fading_piece {
+        draw_piece(ctx, canvas, resources, piece, (x, y), opacity);
+    }
+    // moving pieces are drawn separately, once every square has been drawn
+    if let (Some(piece), None) = (draw_state.piece, draw_state.piece_offset) {
+        draw_piece(ctx, canvas, resources, piece, (x, y), 1_f32);
+    }
+    Ok(())
+}
+
+/// Draw arrows and marked squares on top of the board
+pub fn draw_annotations<'a>(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
+                            annotations: impl Iterator<Item = &'a Annotation>,
+                            render_flipped: bool) -> ggez::GameResult
+{
+    let square_params = util::square_draw_param(ctx);
+    let square_center = |square: Position| {
+        let (x, y) = square_board_pos((square.column.get(), square.row.get()), render_flipped);
+        [x + 0.5_f32, y + 0.5_f32]
+    };
+    for annotation in annotations {
+        match *annotation {
+            Annotation::Mark { square, color } => {
+                let [x, y] = square_center(square);
+                util::draw_ring(ctx, canvas, square_params, colors::annotation_color(color),
+                                x, y, MARK_RADIUS - MARK_THICKNESS * 0.5_f32, MARK_THICKNESS)?;
+            },
+            Annotation::Arrow { source, dest, color } => {
+                let color = colors::annotation_color(color);
+                let (from, to) = (square_center(source), square_center(dest));
+                let length = f32::hypot(to[0] - from[0], to[1] - from[1]);
+                if length <= ARROW_HEAD_LENGTH + ARROW_TIP_INSET { continue; }
+                let direction = [(to[0] - from[0]) / length, (to[1] - from[1]) / length];
+                let normal = [-direction[1], direction[0]];
+                let tip = [to[0] - direction[0] * ARROW_TIP_INSET, to[1] - direction[1] * ARROW_TIP_INSET];
+                let base = [tip[0] - direction[0] * ARROW_HEAD_LENGTH,
+                            tip[1] - direction[1] * ARROW_HEAD_LENGTH];
+                let half_width = ARROW_HEAD_WIDTH * 0.5_f32;
+                util::draw_line(ctx, canvas, square_params, color, from, base, ARROW_WIDTH)?;
+                util::draw_polygon(ctx, canvas, square_params, color, &[
+                    tip,
+                    [base[0] + normal[0] * half_width, base[1] + normal[1] * half_width],
+                    [base[0] - normal[0] * half_width, base[1] - normal[1] * half_width],
+                ])?;
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Draw the file label in the bottom right corner of the squares along the bottom edge of the
+/// board, and the rank label in the top left corner of the squares along the left edge
+fn draw_coordinate_labels(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
+                          index: (u8, u8), render_flipped: bool) -> ggez::GameResult
+{
+    let (x, y) = square_board_pos(index, render_flipped);
+    let (left_column, bottom_row) = if render_flipped { (7, 7) } else { (0, 0) };
+    let text_color = colors::square_text_color(util::is_dark_square(index));
+    let text_scale = gui::util::square_size(ctx) * COORDINATE_TEXT_SIZE;
+
+    if index.1 == bottom_row {
+        let mut text = graphics::Text::new(((b'a' + index.0) as char).to_string());
+        text.set_scale(PxScale::from(text_scale));
+        let [w, h] = text.measure(ctx)?.into();
+        let text_align = TextAlign {
+            horizontal: TextAlignHorizontal::Right,
+            vertical: TextAlignVertical::Bottom,
+        };
+        let relative_pos = (x + 1_f32 - COORDINATE_TEXT_MARGIN, y + 1_f32 - COORDINATE_TEXT_MARGIN);
+        let params = util::board_relative_text_param(ctx, (w, h), text_align, relative_pos);
+        canvas.draw(&text, params.color(text_color));
+    }
+    if index.0 == left_column {
+        let mut text = graphics::Text::new((index.1 + 1).to_string());
+        text.set_scale(PxScale::from(text_scale));
+        let [w, h] = text.measure(ctx)?.into();
+        let text_align = TextAlign {
+            horizontal: TextAlignHorizontal::Left,
+            vertical: TextAlignVertical::Top,
+        };
+        let relative_pos = (x + COORDINATE_TEXT_MARGIN, y + COORDINATE_TEXT_MARGIN);
+        let params = util::board_relative_text_param(ctx, (w, h), text_align, relative_pos);
+        canvas.draw(&text, params.color(text_color));
+    }
+    Ok(())
+}
+
+/// Draw the pieces captured by a player beside the board, on that player's side, followed by their
+/// material advantage if they are ahead
+///
+/// # Arguments
+///
+/// * `captured`: The captured pieces, in the order they should be drawn
+/// * `advantage`: How much more material the player has than the opponent
+/// * `at_top`: Whether the player is at the top of the board
+pub fn draw_captured_pieces(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
+                            resources: &ImageResources, captured: &[Piece], advantage: u32,
+                            at_top: bool) -> ggez::GameResult
+{
+    // slots are filled from the player's edge of the board towards the middle
+    let slot_center = |slot: usize| {
+        let column = (slot % CAPTURED_PIECES_PER_ROW) as f32;
+        let row = (slot / CAPTURED_PIECES_PER_ROW) as f32;
+        let x = CAPTURED_PIECES_X + (column + 0.5_f32) * CAPTURED_PIECE_SIZE;
+        let y = 4.15_f32 - (row + 0.5_f32) * CAPTURED_PIECE_SIZE;
+        (x, if at_top { -y } else { y })
+    };
+
+    for (slot, piece) in captured.iter().enumerate() {
+        let image = &resources.get_piece(*piece).resource;
         let image_size = (image.width() as f32, image.height() as f32);
         let image_params = util::board_relative_draw_param(
-            ctx, image_size, (x + 0.5_f32, y + 0.5_f32), 0.9_f32
+            ctx, image_size, slot_center(slot), CAPTURED_PIECE_SIZE
         );
         canvas.draw(image, image_params);
     }
+
+    if advantage > 0 {
+        let mut text = graphics::Text::new(format!("+{advantage}"));
+        text.set_scale(PxScale::from(gui::util::square_size(ctx) * CAPTURED_PIECE_SIZE * 0.8));
+        let [w, h] = text.measure(ctx)?.into();
+        let text_align = TextAlign {
+            horizontal: TextAlignHorizontal::Left,
+            vertical: TextAlignVertical::Middle,
+        };
+        let (x, y) = slot_center(captured.len());
+        let relative_pos = (x - CAPTURED_PIECE_SIZE * 0.5_f32, y);
+        let params = util::board_relative_text_param(ctx, (w, h), text_align, relative_pos);
+        canvas.draw(&text, params.color(MATERIAL_TEXT_COLOR));
+    }
     Ok(())
 }
 
@@ -195,3 +456,70 @@ pub fn draw_status_text(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
     canvas.draw(&text, params);
     Ok(())
 }
+
+/// Draw a highlighted line of text underneath the status text, used for errors and other notices
+pub fn draw_notice_text(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
+                        text: &str) -> ggez::GameResult
+{
+    let mut text = graphics::Text::new(text);
+    text.set_scale(PxScale::from(18_f32));
+    let [w, h] = text.measure(ctx)?.into();
+    let text_align = TextAlign {
+        horizontal: TextAlignHorizontal::Middle,
+        vertical: TextAlignVertical::Top,
+    };
+    let relative_pos = (0_f32, 5_f32);
+    let params = util::board_relative_text_param(ctx, (w, h), text_align, relative_pos);
+    canvas.draw(&text, params.color(NOTICE_TEXT_COLOR));
+    Ok(())
+}
+
+/// Draw the text box for typing moves underneath the notice text, followed by why the last typed
+/// move was rejected, if it was
+pub fn draw_move_input(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas, input: &str,
+                       error: Option<&str>) -> ggez::GameResult
+{
+    let square_params = util::square_draw_param(ctx);
+    util::draw_rect(ctx, canvas, square_params, MOVE_INPUT_BACKGROUND_COLOR,
+                    -4.15_f32, 5.4_f32, 8.3_f32, 0.45_f32)?;
+
+    let mut text = graphics::Text::new(format!("Move: {input}_"));
+    text.set_scale(PxScale::from(18_f32));
+    let [w, h] = text.measure(ctx)?.into();
+    let text_align = TextAlign {
+        horizontal: TextAlignHorizontal::Left,
+        vertical: TextAlignVertical::Middle,
+    };
+    let params = util::board_relative_text_param(ctx, (w, h), text_align, (-4_f32, 5.625_f32));
+    canvas.draw(&text, params.color(MOVE_INPUT_TEXT_COLOR));
+
+    if let Some(error) = error {
+        let mut text = graphics::Text::new(error);
+        text.set_scale(PxScale::from(18_f32));
+        let [w, h] = text.measure(ctx)?.into();
+        let text_align = TextAlign {
+            horizontal: TextAlignHorizontal::Right,
+            vertical: TextAlignVertical::Middle,
+        };
+        let params = util::board_relative_text_param(ctx, (w, h), text_align, (4_f32, 5.625_f32));
+        canvas.draw(&text, params.color(NOTICE_TEXT_COLOR));
+    }
+    Ok(())
+}
+
+/// Draw information about the network connection above the board
+pub fn draw_connection_text(ctx: &mut ggez::Context, canvas: &mut graphics::Canvas,
+                            text: &str) -> ggez::GameResult
+{
+    let mut text = graphics::Text::new(text);
+    text.set_scale(PxScale::from(16_f32));
+    let [w, h] = text.measure(ctx)?.into();
+    let text_align = TextAlign {
+        horizontal: TextAlignHorizontal::Right,
+        vertical: TextAlignVertical::Bottom,
+    };
+    let relative_pos = (4.15_f32, -4.25_f32);
+    let params = util::board_relative_text_param(ctx, (w, h), text_align, relative_pos);
+    canvas.draw(&text, params.color(CONNECTION_TEXT_COLOR));
+    Ok(())
+}
@@ -1,6 +1,6 @@
-use std::num::ParseIntError;
 use ggez::graphics::Color;
-use crate::gui::drawing::{SquareDrawColor, SquareDrawState};
+use crate::gui::drawing::{KingHighlight, SquareDrawColor};
+use crate::network::chess_tp::AnnotationColor;
 
 /// Convert hex code to `Color` object
 const fn hex(s: &str) -> Color {
@@ -27,6 +27,11 @@ const fn mult(c: Color, factor: f32) -> Color {
     Color::new(c.r * factor, c.g * factor, c.b * factor, c.a)
 }
 
+/// Replace the alpha component of the color object
+const fn with_alpha(c: Color, alpha: f32) -> Color {
+    Color::new(c.r, c.g, c.b, alpha)
+}
+
 pub const BACKGROUND_COLOR: Color = from_rgb(30, 30, 30);
 pub const BOARD_BORDER_COLOR: Color = hex("552C25");
 
@@ -40,20 +45,49 @@ pub const DARK_SELECTED_SQUARE_BORDER_COLOR: Color = mult(DARK_SELECTED_SQUARE_C
 pub const LIGHT_SELECTED_SQUARE_COLOR: Color = mult(DARK_SELECTED_SQUARE_COLOR, 1.1);
 pub const LIGHT_SELECTED_SQUARE_BORDER_COLOR: Color = mult(LIGHT_SELECTED_SQUARE_COLOR, 0.9);
 
+pub const DARK_LAST_MOVE_SQUARE_COLOR: Color = hex("3AA89B");
+pub const DARK_LAST_MOVE_SQUARE_BORDER_COLOR: Color = mult(DARK_LAST_MOVE_SQUARE_COLOR, 0.9);
+pub const LIGHT_LAST_MOVE_SQUARE_COLOR: Color = hex("8ED4C6");
+pub const LIGHT_LAST_MOVE_SQUARE_BORDER_COLOR: Color = mult(LIGHT_LAST_MOVE_SQUARE_COLOR, 0.9);
+
+pub const DARK_PREMOVE_SQUARE_COLOR: Color = hex("A8563A");
+pub const DARK_PREMOVE_SQUARE_BORDER_COLOR: Color = mult(DARK_PREMOVE_SQUARE_COLOR, 0.9);
+pub const LIGHT_PREMOVE_SQUARE_COLOR: Color = hex("D49A8E");
+pub const LIGHT_PREMOVE_SQUARE_BORDER_COLOR: Color = mult(LIGHT_PREMOVE_SQUARE_COLOR, 0.9);
+
 pub const PROMOTION_SELECTION_SQUARE_COLOR: Color = hex("FFEBD6");
 pub const PROMOTION_SELECTION_SQUARE_BORDER_COLOR: Color = mult(PROMOTION_SELECTION_SQUARE_COLOR, 0.9);
 
+/// Color of each layer of the glow around a king in check, which add up towards the center
+pub const CHECK_GLOW_COLOR: Color = with_alpha(hex("FF3B30"), 0.25);
+pub const CHECKMATE_GLOW_COLOR: Color = with_alpha(hex("9E0B0F"), 0.4);
+
+pub const GREEN_ANNOTATION_COLOR: Color = with_alpha(hex("15781B"), 0.8);
+pub const RED_ANNOTATION_COLOR: Color = with_alpha(hex("882020"), 0.8);
+pub const BLUE_ANNOTATION_COLOR: Color = with_alpha(hex("003088"), 0.8);
+pub const YELLOW_ANNOTATION_COLOR: Color = with_alpha(hex("E68F00"), 0.8);
+
 pub const DARK_SQUARE_TEXT_COLOR: Color = LIGHT_SQUARE_COLOR;
 pub const LIGHT_SQUARE_TEXT_COLOR: Color = DARK_SQUARE_COLOR;
 
-pub fn square_colors(is_dark_square: bool, square_draw_color: SquareDrawColor) -> (Color, Color) {
+pub const NOTICE_TEXT_COLOR: Color = hex("E8735A");
+pub const CONNECTION_TEXT_COLOR: Color = from_rgb(150, 150, 150);
+pub const MATERIAL_TEXT_COLOR: Color = from_rgb(200, 200, 200);
+pub const MOVE_INPUT_BACKGROUND_COLOR: Color = from_rgb(50, 50, 50);
+pub const MOVE_INPUT_TEXT_COLOR: Color = from_rgb(230, 230, 230);
+
+pub(super) fn square_colors(is_dark_square: bool, square_draw_color: SquareDrawColor) -> (Color, Color) {
     match square_draw_color {
         SquareDrawColor::Normal | SquareDrawColor::Targeted =>
             (normal_square_color(is_dark_square), normal_square_border_color(is_dark_square)),
         SquareDrawColor::Selected =>
             (selected_square_color(is_dark_square), selected_square_border_color(is_dark_square)),
         SquareDrawColor::PromotionSelection =>
-            (PROMOTION_SELECTION_SQUARE_COLOR, PROMOTION_SELECTION_SQUARE_BORDER_COLOR)
+            (PROMOTION_SELECTION_SQUARE_COLOR, PROMOTION_SELECTION_SQUARE_BORDER_COLOR),
+        SquareDrawColor::LastMove =>
+            (last_move_square_color(is_dark_square), last_move_square_border_color(is_dark_square)),
+        SquareDrawColor::Premove =>
+            (premove_square_color(is_dark_square), premove_square_border_color(is_dark_square)),
     }
 }
 
@@ -74,6 +108,40 @@ pub fn selected_square_border_color(is_dark_square: bool) -> Color {
     else { LIGHT_SELECTED_SQUARE_BORDER_COLOR }
 }
 
+pub fn last_move_square_color(is_dark_square: bool) -> Color {
+    if is_dark_square { DARK_LAST_MOVE_SQUARE_COLOR } else { LIGHT_LAST_MOVE_SQUARE_COLOR }
+}
+
+pub fn last_move_square_border_color(is_dark_square: bool) -> Color {
+    if is_dark_square { DARK_LAST_MOVE_SQUARE_BORDER_COLOR }
+    else { LIGHT_LAST_MOVE_SQUARE_BORDER_COLOR }
+}
+
+pub fn premove_square_color(is_dark_square: bool) -> Color {
+    if is_dark_square { DARK_PREMOVE_SQUARE_COLOR } else { LIGHT_PREMOVE_SQUARE_COLOR }
+}
+
+pub fn premove_square_border_color(is_dark_square: bool) -> Color {
+    if is_dark_square { DARK_PREMOVE_SQUARE_BORDER_COLOR }
+    else { LIGHT_PREMOVE_SQUARE_BORDER_COLOR }
+}
+
+pub fn king_glow_color(king_highlight: KingHighlight) -> Color {
+    match king_highlight {
+        KingHighlight::Check => CHECK_GLOW_COLOR,
+        KingHighlight::Checkmate => CHECKMATE_GLOW_COLOR,
+    }
+}
+
+pub fn annotation_color(color: AnnotationColor) -> Color {
+    match color {
+        AnnotationColor::Green => GREEN_ANNOTATION_COLOR,
+        AnnotationColor::Red => RED_ANNOTATION_COLOR,
+        AnnotationColor::Blue => BLUE_ANNOTATION_COLOR,
+        AnnotationColor::Yellow => YELLOW_ANNOTATION_COLOR,
+    }
+}
+
 pub fn square_text_color(is_dark_square: bool) -> Color {
     if is_dark_square { DARK_SQUARE_TEXT_COLOR } else { LIGHT_SQUARE_TEXT_COLOR }
 }
@@ -7,7 +7,7 @@ const CIRCLE_TOLERANCE: f32 = 0.002_f32;
 
 /// returns: Whether the square at the given index is a dark square
 pub fn is_dark_square(square_index: (u8, u8)) -> bool {
-    (square_index.0 + square_index.1) % 2 == 0
+    (square_index.0 + square_index.1).is_multiple_of(2)
 }
 
 /// returns: The [DrawParam] that transforms positions from the board coordinate space to screen
@@ -75,6 +75,7 @@ pub fn board_relative_text_param(ctx: &ggez::Context, element_size: (f32, f32),
         ])
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_rect(ctx: &ggez::Context, canvas: &mut graphics::Canvas, params: DrawParam,
                  color: graphics::Color, x: f32, y: f32, w: f32, h: f32) -> ggez::GameResult
 {
@@ -85,6 +86,33 @@ pub fn draw_rect(ctx: &ggez::Context, canvas: &mut graphics::Canvas, params: Dra
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn draw_ring(ctx: &ggez::Context, canvas: &mut graphics::Canvas, params: DrawParam,
+                 color: graphics::Color, x: f32, y: f32, r: f32, thickness: f32) -> ggez::GameResult
+{
+    let ring = graphics::Mesh::new_circle(
+        ctx, graphics::DrawMode::stroke(thickness), [x, y], r, CIRCLE_TOLERANCE, color
+    )?;
+    canvas.draw(&ring, params);
+    Ok(())
+}
+
+pub fn draw_line(ctx: &ggez::Context, canvas: &mut graphics::Canvas, params: DrawParam,
+                 color: graphics::Color, from: [f32; 2], to: [f32; 2], width: f32) -> ggez::GameResult
+{
+    let line = graphics::Mesh::new_line(ctx, &[from, to], width, color)?;
+    canvas.draw(&line, params);
+    Ok(())
+}
+
+pub fn draw_polygon(ctx: &ggez::Context, canvas: &mut graphics::Canvas, params: DrawParam,
+                    color: graphics::Color, points: &[[f32; 2]]) -> ggez::GameResult
+{
+    let polygon = graphics::Mesh::new_polygon(ctx, graphics::DrawMode::fill(), points, color)?;
+    canvas.draw(&polygon, params);
+    Ok(())
+}
+
 pub fn draw_circle(ctx: &ggez::Context, canvas: &mut graphics::Canvas, params: DrawParam,
                    color: graphics::Color, x: f32, y: f32, r: f32) -> ggez::GameResult
 {
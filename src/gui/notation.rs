@@ -0,0 +1,236 @@
+use rsoderh_chess::{Color, Game, HalfMoveRequest, PieceKind, Position};
+use crate::gui::util;
+
+/// Parse a move typed by the player, in standard algebraic notation such as `Nf3`, `exd5`,
+/// `e8=Q` or `O-O`, or in UCI notation such as `g1f3` or `e7e8q`, and check that it is valid in
+/// the current position
+///
+/// returns: The move, or a description of why it is not valid
+pub fn parse_move(game: &Game, text: &str) -> Result<HalfMoveRequest, String> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    if text.is_empty() {
+        return Err("Type a move, such as Nf3, e2e4 or O-O".to_owned());
+    }
+    if let Some((source, dest, promotion)) = parse_uci(text) {
+        return validate_move(game, source, dest, promotion, text);
+    }
+    parse_san(game, text)
+}
+
+/// returns: The name of `square` in algebraic notation, such as `e4`
+pub fn square_name(square: Position) -> String {
+    format!("{}{}", (b'a' + square.column.get()) as char, square.row.get() + 1)
+}
+
+fn parse_square(square: &[u8]) -> Option<Position> {
+    let [file, rank] = square else { return None; };
+    if !(b'a'..=b'h').contains(file) || !(b'1'..=b'8').contains(rank) { return None; }
+    Position::new(file - b'a', rank - b'1')
+}
+
+fn parse_promotion_kind(ch: u8) -> Option<PieceKind> {
+    match ch.to_ascii_uppercase() {
+        b'N' => Some(PieceKind::Knight),
+        b'B' => Some(PieceKind::Bishop),
+        b'R' => Some(PieceKind::Rook),
+        b'Q' => Some(PieceKind::Queen),
+        _ => None,
+    }
+}
+
+fn parse_uci(text: &str) -> Option<(Position, Position, Option<PieceKind>)> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 4 && bytes.len() != 5 { return None; }
+    let source = parse_square(&bytes[0..2])?;
+    let dest = parse_square(&bytes[2..4])?;
+    let promotion = match bytes.get(4) {
+        None => None,
+        Some(ch) => Some(parse_promotion_kind(*ch)?),
+    };
+    Some((source, dest, promotion))
+}
+
+fn parse_san(game: &Game, text: &str) -> Result<HalfMoveRequest, String> {
+    let back_rank = match game.turn {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    match text {
+        "O-O" | "0-0" => {
+            let (source, dest) = (Position::new(4, back_rank), Position::new(6, back_rank));
+            return validate_move(game, source.unwrap(), dest.unwrap(), None, text);
+        },
+        "O-O-O" | "0-0-0" => {
+            let (source, dest) = (Position::new(4, back_rank), Position::new(2, back_rank));
+            return validate_move(game, source.unwrap(), dest.unwrap(), None, text);
+        },
+        _ => {},
+    }
+
+    let invalid = || format!("\"{text}\" is not a valid move");
+    let (body, promotion) = match text.split_once('=') {
+        Some((body, promotion)) => {
+            let [promotion] = promotion.as_bytes() else { return Err(invalid()); };
+            (body, Some(parse_promotion_kind(*promotion).ok_or_else(invalid)?))
+        },
+        // the `=` may be left out, as in `e8Q`
+        None => match text.as_bytes() {
+            [.., rank, promotion] if rank.is_ascii_digit() && promotion.is_ascii_uppercase() => {
+                let kind = parse_promotion_kind(*promotion).ok_or_else(invalid)?;
+                (&text[..text.len() - 1], Some(kind))
+            },
+            _ => (text, None),
+        },
+    };
+    let (kind, rest) = match body.as_bytes().first() {
+        Some(b'N') => (PieceKind::Knight, &body[1..]),
+        Some(b'B') => (PieceKind::Bishop, &body[1..]),
+        Some(b'R') => (PieceKind::Rook, &body[1..]),
+        Some(b'Q') => (PieceKind::Queen, &body[1..]),
+        Some(b'K') => (PieceKind::King, &body[1..]),
+        _ => (PieceKind::Pawn, body),
+    };
+    let rest: Vec<u8> = rest.bytes().filter(|ch| *ch != b'x').collect();
+    if rest.len() < 2 || rest.len() > 4 { return Err(invalid()); }
+    let (disambiguation, dest) = rest.split_at(rest.len() - 2);
+    let dest = parse_square(dest).ok_or_else(invalid)?;
+    let mut source_file = None;
+    let mut source_rank = None;
+    for ch in disambiguation {
+        match ch {
+            b'a'..=b'h' if source_file.is_none() => source_file = Some(ch - b'a'),
+            b'1'..=b'8' if source_rank.is_none() => source_rank = Some(ch - b'1'),
+            _ => return Err(invalid()),
+        }
+    }
+
+    let candidates: Vec<Position> = (0..64_u8)
+        .map(|index| Position::new(index % 8, index / 8).unwrap())
+        .filter(|source| source_file.is_none_or(|file| source.column.get() == file))
+        .filter(|source| source_rank.is_none_or(|rank| source.row.get() == rank))
+        .filter(|source| game.board().at_position(*source).as_piece().is_some_and(|piece| {
+            piece.color == game.turn && util::same_kind(piece.kind, kind)
+        }))
+        .filter(|source| game.valid_moves(*source).is_some_and(|moves| moves.contains(&dest)))
+        .collect();
+    match candidates[..] {
+        [source] => validate_move(game, source, dest, promotion, text),
+        [] => Err(format!("\"{text}\" is not a legal move")),
+        _ => Err(format!("\"{text}\" is ambiguous, add the file or rank of the piece to move")),
+    }
+}
+
+/// returns: The move of the piece at `source` to `dest`, if it is legal and promotes exactly when
+///          a pawn reaches the last rank
+pub fn validate_move(game: &Game, source: Position, dest: Position, promotion: Option<PieceKind>,
+                     text: &str) -> Result<HalfMoveRequest, String>
+{
+    let Some(piece) = game.board().at_position(source).as_piece().copied()
+        .filter(|piece| piece.color == game.turn)
+    else {
+        return Err(format!("\"{text}\" does not move one of your pieces"));
+    };
+    if !game.valid_moves(source).is_some_and(|moves| moves.contains(&dest)) {
+        return Err(format!("\"{text}\" is not a legal move"));
+    }
+    let is_promotion = matches!(piece.kind, PieceKind::Pawn)
+        && (dest.row.get() == 0 || dest.row.get() == 7);
+    match (is_promotion, promotion) {
+        (true, Some(kind)) => Ok(HalfMoveRequest::Promotion { column: dest.column, kind }),
+        (true, None) => Err(format!("\"{text}\" needs a promotion piece, such as =Q or q")),
+        (false, Some(_)) => Err(format!("\"{text}\" is not a promotion")),
+        (false, None) => Ok(HalfMoveRequest::Standard { source, dest }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsoderh_chess::MoveResult;
+    use crate::gui::util::test_util::{board_with, square};
+    use super::*;
+
+    fn standard_move(game: &Game, text: &str) -> Option<(Position, Position)> {
+        match parse_move(game, text) {
+            Ok(HalfMoveRequest::Standard { source, dest }) => Some((source, dest)),
+            _ => None,
+        }
+    }
+
+    /// returns: A position with white to move, where white can promote on e8, capture on d5 and
+    ///          has two knights that reach d2 and two rooks that reach a3
+    fn middlegame() -> Game {
+        use Color::*;
+        use PieceKind::*;
+        Game::new(board_with(&[
+            ("e1", White, King), ("a1", White, Rook), ("a5", White, Rook), ("b1", White, Knight),
+            ("f3", White, Knight), ("e4", White, Pawn), ("e7", White, Pawn),
+            ("c8", Black, King), ("d5", Black, Pawn),
+        ]), White)
+    }
+
+    #[test]
+    fn parses_san_and_uci() {
+        let game = Game::new_standard();
+        assert_eq!(standard_move(&game, "Nf3"), Some((square("g1"), square("f3"))));
+        assert_eq!(standard_move(&game, "e4"), Some((square("e2"), square("e4"))));
+        assert_eq!(standard_move(&game, "e2e4"), Some((square("e2"), square("e4"))));
+        assert_eq!(standard_move(&game, "Ngf3+"), Some((square("g1"), square("f3"))));
+    }
+
+    #[test]
+    fn parses_castling() {
+        let mut game = Game::new_standard();
+        for text in ["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5"] {
+            let chess_move = parse_move(&game, text).unwrap();
+            let MoveResult::Ongoing(next, ..) = game.perform_move(chess_move) else {
+                panic!("{text} should not end the game");
+            };
+            game = next;
+        }
+        assert_eq!(standard_move(&game, "O-O"), Some((square("e1"), square("g1"))));
+        assert_eq!(standard_move(&game, "0-0"), Some((square("e1"), square("g1"))));
+        assert!(parse_move(&game, "O-O-O").is_err());
+    }
+
+    #[test]
+    fn parses_promotions() {
+        let game = middlegame();
+        for text in ["e8=Q", "e8Q", "e7e8q", "e8=Q+"] {
+            assert!(matches!(parse_move(&game, text), Ok(HalfMoveRequest::Promotion {
+                column, kind: PieceKind::Queen,
+            }) if column.get() == 4), "{text}");
+        }
+        assert!(matches!(parse_move(&game, "e8=N"),
+                         Ok(HalfMoveRequest::Promotion { kind: PieceKind::Knight, .. })));
+        assert!(parse_move(&game, "e8").is_err());
+        assert!(parse_move(&game, "e7e8").is_err());
+    }
+
+    #[test]
+    fn parses_captures_and_disambiguation() {
+        let game = middlegame();
+        assert_eq!(standard_move(&game, "exd5"), Some((square("e4"), square("d5"))));
+        assert_eq!(standard_move(&game, "Nbd2"), Some((square("b1"), square("d2"))));
+        assert_eq!(standard_move(&game, "Nfd2"), Some((square("f3"), square("d2"))));
+        assert_eq!(standard_move(&game, "R1a3"), Some((square("a1"), square("a3"))));
+        assert_eq!(standard_move(&game, "R5a3"), Some((square("a5"), square("a3"))));
+        assert_eq!(standard_move(&game, "Nf3d2"), Some((square("f3"), square("d2"))));
+    }
+
+    #[test]
+    fn rejects_ambiguous_moves() {
+        let game = middlegame();
+        for text in ["Nd2", "Ra3"] {
+            let Err(error) = parse_move(&game, text) else { panic!("{text} should be ambiguous") };
+            assert!(error.contains("ambiguous"), "{text}: {error}");
+        }
+    }
+
+    #[test]
+    fn rejects_illegal_and_malformed_moves() {
+        let game = Game::new_standard();
+        for text in ["Nf6", "e5", "O-O", "e7e5", "Ke2", "Zf3", "e9", "", "e2e4q"] {
+            assert!(parse_move(&game, text).is_err(), "{text}");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use rsoderh_chess::{Color, PieceKind, Position};
+use rsoderh_chess::{Board, Color, Game, HalfMoveRequest, Piece, PieceKind, Position};
 
 const MIN_SIZE_IN_SQUARES: f32 = 12_f32;
 const MIN_SQUARE_SIZE: f32 = 40_f32;
@@ -11,7 +11,7 @@ pub fn square_size(ctx: &ggez::Context) -> f32 {
         screen_size.0 / MIN_SIZE_IN_SQUARES,
         screen_size.1 / MIN_SIZE_IN_SQUARES,
     );
-    f32::min(f32::max(square_size, MIN_SQUARE_SIZE), MAX_SQUARE_SIZE)
+    square_size.clamp(MIN_SQUARE_SIZE, MAX_SQUARE_SIZE)
 }
 
 /// returns: A position relative to the center of the screen, converted to screen coordinate space
@@ -73,7 +73,7 @@ pub fn promotion_selection_iter(to_play: Color, square: Position)
     let file = square.column.get();
     ranks.into_iter()
         .map(move |rank| Position::from_pair((file, rank)).unwrap())
-        .zip(piece_types.into_iter())
+        .zip(piece_types)
 }
 
 /// returns: Which piece type should be displayed at the provided `square`, if it is part of the
@@ -84,3 +84,242 @@ pub fn promotion_selection_type(turn: Color, promotion_square: Position,
     let mut iter = promotion_selection_iter(turn, promotion_square);
     iter.find_map(|(pos, piece_type)| (pos == square).then_some(piece_type))
 }
+
+/// returns: The source and destination squares of a move that is about to be performed, or `None`
+///          if no piece can make the move. The source of a promotion is the pawn that can reach
+///          the promotion square, which may be on a neighboring file when capturing.
+pub fn move_squares(game: &Game, chess_move: &HalfMoveRequest) -> Option<(Position, Position)> {
+    match chess_move {
+        HalfMoveRequest::Standard { source, dest } => Some((*source, *dest)),
+        HalfMoveRequest::Promotion { column, .. } => {
+            let (source_rank, dest_rank) = match game.turn {
+                Color::White => (6, 7),
+                Color::Black => (1, 0),
+            };
+            let dest = Position::new(column.get(), dest_rank)?;
+            let source = [column.get().checked_sub(1), Some(column.get()), Some(column.get() + 1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|file| Position::new(file, source_rank))
+                .find(|source| {
+                    let is_own_pawn = game.board().at_position(*source).as_piece().is_some_and(|piece| {
+                        piece.color == game.turn && matches!(piece.kind, PieceKind::Pawn)
+                    });
+                    is_own_pawn && game.valid_moves(*source).is_some_and(|moves| moves.contains(&dest))
+                })?;
+            Some((source, dest))
+        },
+    }
+}
+
+/// returns: Whether moving the piece at `source` to `dest` captures a piece, including en passant
+pub fn is_capture(board: &Board, source: Position, dest: Position) -> bool {
+    let is_pawn = board.at_position(source).as_piece()
+        .is_some_and(|piece| matches!(piece.kind, PieceKind::Pawn));
+    let is_diagonal = source.column.get() != dest.column.get();
+    board.at_position(dest).as_piece().is_some() || (is_pawn && is_diagonal)
+}
+
+/// returns: Whether moving the piece at `source` to `dest` is a castling move
+pub fn is_castling(board: &Board, source: Position, dest: Position) -> bool {
+    let is_king = board.at_position(source).as_piece()
+        .is_some_and(|piece| matches!(piece.kind, PieceKind::King));
+    is_king && source.column.get().abs_diff(dest.column.get()) == 2
+}
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] =
+    [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS: [(i8, i8); 8] =
+    [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+const ORTHOGONAL_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONAL_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+/// returns: The square at the given offset from `square`, if it is on the board
+fn offset_square(square: Position, offset: (i8, i8)) -> Option<Position> {
+    let column = square.column.get().checked_add_signed(offset.0)?;
+    let row = square.row.get().checked_add_signed(offset.1)?;
+    Position::new(column, row)
+}
+
+/// returns: Whether `a` and `b` are the same kind of piece
+pub fn same_kind(a: PieceKind, b: PieceKind) -> bool {
+    std::mem::discriminant(&a) == std::mem::discriminant(&b)
+}
+
+/// returns: Whether any piece of color `attacker` attacks `square`, regardless of whether moving
+///          that piece would leave its own king in check
+pub fn is_square_attacked(board: &Board, square: Position, attacker: Color) -> bool {
+    let is_attacker = |pos: Position, kinds: &[PieceKind]| {
+        board.at_position(pos).as_piece().is_some_and(|piece| {
+            piece.color == attacker
+                && kinds.iter().any(|kind| same_kind(*kind, piece.kind))
+        })
+    };
+    let attacked_by_step = |offsets: &[(i8, i8)], kinds: &[PieceKind]| {
+        offsets.iter()
+            .filter_map(|offset| offset_square(square, *offset))
+            .any(|pos| is_attacker(pos, kinds))
+    };
+    let attacked_by_slide = |directions: &[(i8, i8)], kinds: &[PieceKind]| {
+        directions.iter().any(|direction| {
+            let mut pos = square;
+            while let Some(next) = offset_square(pos, *direction) {
+                if board.at_position(next).as_piece().is_some() {
+                    return is_attacker(next, kinds);
+                }
+                pos = next;
+            }
+            false
+        })
+    };
+
+    // pawns attack diagonally forward, so an attacking pawn is diagonally behind the square
+    let pawn_row_offset = match attacker {
+        Color::White => -1,
+        Color::Black => 1,
+    };
+    attacked_by_step(&[(-1, pawn_row_offset), (1, pawn_row_offset)], &[PieceKind::Pawn])
+        || attacked_by_step(&KNIGHT_OFFSETS, &[PieceKind::Knight])
+        || attacked_by_step(&KING_OFFSETS, &[PieceKind::King])
+        || attacked_by_slide(&ORTHOGONAL_DIRECTIONS, &[PieceKind::Rook, PieceKind::Queen])
+        || attacked_by_slide(&DIAGONAL_DIRECTIONS, &[PieceKind::Bishop, PieceKind::Queen])
+}
+
+pub fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// returns: Every square of the board
+pub fn all_squares() -> impl Iterator<Item = Position> {
+    (0..8_u8).flat_map(|column| (0..8_u8).map(move |row| Position::new(column, row).unwrap()))
+}
+
+/// returns: The square of the king of the given color, if it is in check
+pub fn checked_king(board: &Board, color: Color) -> Option<Position> {
+    all_squares()
+        .find(|pos| board.at_position(*pos).as_piece().is_some_and(|piece| {
+            piece.color == color && matches!(piece.kind, PieceKind::King)
+        }))
+        .filter(|king| is_square_attacked(board, *king, opponent(color)))
+}
+
+/// Piece kinds that can be captured, in order of increasing value, along with how many of them
+/// each player starts with
+const CAPTURABLE_PIECES: [(PieceKind, u8); 5] = [
+    (PieceKind::Pawn, 8), (PieceKind::Knight, 2), (PieceKind::Bishop, 2),
+    (PieceKind::Rook, 2), (PieceKind::Queen, 1),
+];
+
+/// returns: The conventional material value of a piece kind, where kings have no value
+pub fn piece_value(kind: PieceKind) -> u32 {
+    match kind {
+        PieceKind::Pawn => 1,
+        PieceKind::Knight | PieceKind::Bishop => 3,
+        PieceKind::Rook => 5,
+        PieceKind::Queen => 9,
+        PieceKind::King => 0,
+    }
+}
+
+/// returns: The total value of the pieces of the given color on the board
+pub fn material(board: &Board, color: Color) -> u32 {
+    all_squares()
+        .filter_map(|pos| board.at_position(pos).as_piece().copied())
+        .filter(|piece| piece.color == color)
+        .map(|piece| piece_value(piece.kind))
+        .sum()
+}
+
+/// returns: The pieces of the given color that are missing from the board compared to the starting
+///          position, sorted by increasing value. Pawns that have promoted are counted as captured,
+///          and promoted pieces make up for captured pieces of the same kind.
+pub fn captured_pieces(board: &Board, color: Color) -> Vec<Piece> {
+    CAPTURABLE_PIECES.iter()
+        .flat_map(|(kind, starting_count)| {
+            let count = all_squares()
+                .filter_map(|pos| board.at_position(pos).as_piece().copied())
+                .filter(|piece| piece.color == color && same_kind(piece.kind, *kind))
+                .count();
+            let captured = (*starting_count as usize).saturating_sub(count);
+            std::iter::repeat_n(Piece { kind: *kind, color }, captured)
+        })
+        .collect()
+}
+
+/// Helpers for building positions in the tests of the gui modules
+#[cfg(test)]
+pub mod test_util {
+    use rsoderh_chess::{Board, Color, Piece, PieceKind, Position, Slot};
+
+    /// returns: The square with the given name, such as `e4`
+    pub fn square(name: &str) -> Position {
+        let [column, row] = name.as_bytes() else { panic!("invalid square {name}") };
+        Position::new(column - b'a', row - b'1').unwrap()
+    }
+
+    /// returns: An otherwise empty board with the given pieces on it
+    pub fn board_with(pieces: &[(&str, Color, PieceKind)]) -> Board {
+        let mut board = Board::new_empty();
+        for (name, color, kind) in pieces {
+            *board.at_position_mut(square(name)) = Slot::Occupied(Piece { kind: *kind, color: *color });
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gui::util::test_util::{board_with, square};
+    use super::*;
+
+    #[test]
+    fn detects_check_from_each_piece() {
+        use Color::*;
+        use PieceKind::*;
+        let cases = [
+            ("d2", Black, Pawn), ("f2", Black, Pawn), ("d3", Black, Knight), ("f2", Black, King),
+            ("e8", Black, Rook), ("a1", Black, Rook), ("h4", Black, Bishop), ("a5", Black, Queen),
+        ];
+        for (attacker_square, color, kind) in cases {
+            let board = board_with(&[("e1", White, King), (attacker_square, color, kind)]);
+            assert_eq!(checked_king(&board, White), Some(square("e1")), "{attacker_square}");
+        }
+    }
+
+    #[test]
+    fn counts_captured_pieces_and_material() {
+        use Color::*;
+        use PieceKind::*;
+        let board = board_with(&[
+            ("e1", White, King), ("d1", White, Queen), ("a2", White, Pawn),
+            ("e8", Black, King), ("a8", Black, Rook), ("h8", Black, Rook), ("d8", Black, Queen),
+            ("b8", Black, Queen),
+        ]);
+        assert_eq!(material(&board, White), 10);
+        assert_eq!(material(&board, Black), 28);
+        let captured: Vec<u32> = captured_pieces(&board, White).iter()
+            .map(|piece| piece_value(piece.kind))
+            .collect();
+        assert_eq!(captured, [1, 1, 1, 1, 1, 1, 1, 3, 3, 3, 3, 5, 5]);
+        // the promoted queen does not make up for the captured pawn that became it
+        let captured: Vec<u32> = captured_pieces(&board, Black).iter()
+            .map(|piece| piece_value(piece.kind))
+            .collect();
+        assert_eq!(captured, [1, 1, 1, 1, 1, 1, 1, 1, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn ignores_blocked_and_backward_attacks() {
+        use Color::*;
+        use PieceKind::*;
+        let blocked = board_with(&[("e1", White, King), ("e2", White, Pawn), ("e8", Black, Queen)]);
+        assert_eq!(checked_king(&blocked, White), None);
+        let pawn_in_front = board_with(&[("e8", Black, King), ("f7", White, Pawn)]);
+        assert_eq!(checked_king(&pawn_in_front, Black), Some(square("e8")));
+        let pawn_behind = board_with(&[("e4", White, King), ("d3", Black, Pawn)]);
+        assert_eq!(checked_king(&pawn_behind, White), None);
+    }
+}
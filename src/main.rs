@@ -1,12 +1,16 @@
 #![allow(dead_code)]
-#![allow(warnings)]
 
-use std::error::Error;
-use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
 use ggez::conf::{NumSamples, WindowMode, WindowSetup};
-use crate::args::{Cli, Commands};
+use crate::args::{Cli, Commands, ConnectionOptions};
 use crate::gui::GuiState;
 use crate::network::GameConnection;
+use crate::network::conformance::{self, Harness};
+use crate::network::session_log::SessionLog;
+use crate::network::inspect::FrameFormat;
+use crate::network::transport::{FileTransport, FrameMode, Role, Transport};
 use rsoderh_chess::Color;
 
 mod gui;
@@ -22,34 +26,59 @@ const RESOURCES_PATH: &str = "res";
 
 fn main() {
     let cli = <Cli as clap::Parser>::parse();
-    let connection = if let Some(subcommand) = cli.command {
-        match subcommand {
-            Commands::Host {
-                strict,
-                bind_address
-            } => {
-                let listener = TcpListener::bind(&bind_address)
-                    .expect("failed to bind address");
-                println!("Waiting for other player...");
-                let (stream, other_address) = listener.accept()
-                    .expect("failed to accept connection");
-                stream.set_nonblocking(true)
-                    .expect("failed to set stream to non-blocking");
-                println!("Connected to {other_address}");
-                Some(GameConnection::new(stream, Color::Black, strict))
-            },
-            Commands::Join { strict, address } => {
-                println!("Connecting to {}...", &address);
-                let stream = TcpStream::connect(&address)
-                    .expect("failed to connect to remote host");
-                stream.set_nonblocking(true)
-                    .expect("failed to set stream to non-blocking");
-                println!("Connected to {}", &address);
-                Some(GameConnection::new(stream, Color::White, strict))
-            },
-        }
-    } else {
-        None
+    let mut saved_moves = Vec::new();
+    let mut gui_options = cli.gui;
+    let connection = match cli.command {
+        None => None,
+        Some(Commands::Host { bind_address, options, gui }) => {
+            gui_options = gui;
+            println!("Waiting for other player...");
+            let (transport, other_address) = network::transport::host(
+                options.transport, &bind_address, options.passphrase.as_deref(), options.frames)
+                .expect("failed to accept connection");
+            println!("Connected to {other_address}");
+            Some(new_connection(transport, Color::Black, &options, &other_address))
+        },
+        Some(Commands::Join { address, options, gui }) => {
+            gui_options = gui;
+            println!("Connecting to {}...", &address);
+            let transport = network::transport::join(
+                options.transport, &address, options.passphrase.as_deref(), options.frames)
+                .expect("failed to connect to remote host");
+            println!("Connected to {}", &address);
+            Some(new_connection(transport, Color::White, &options, &address))
+        },
+        Some(Commands::Correspondence { file, black, strict, log_dir, gui }) => {
+            gui_options = gui;
+            let frames_seen;
+            (saved_moves, frames_seen) = network::correspondence::open_game_file(&file)
+                .expect("failed to open game file");
+            println!("Opened game file {} with {} moves", file.display(), saved_moves.len());
+            let local_player = if black { Color::Black } else { Color::White };
+            let transport = FileTransport::new(&file, frames_seen);
+            let session_log = open_session_log(
+                &log_dir, local_player, &format!("Opened game file {}", file.display()));
+            let mut connection = GameConnection::new(
+                Box::new(transport), local_player, strict, Duration::MAX, session_log);
+            connection.set_resumable(true);
+            Some(connection)
+        },
+        Some(Commands::Conformance { address, host, options }) => {
+            run_conformance(&address, host, &options);
+            return;
+        },
+        Some(Commands::Proxy { listen_address, host_address, record, options }) => {
+            run_proxy(&listen_address, &host_address, &record, &options);
+            return;
+        },
+        Some(Commands::Replay { recording, address, join, options }) => {
+            run_replay(&recording, &address, join, &options);
+            return;
+        },
+        Some(Commands::Inspect { input, format, frames }) => {
+            run_inspect(&input, format, frames);
+            return;
+        },
     };
 
     let resource_path = std::env::var("CARGO_MANIFEST_DIR").map(|manifest_dir| {
@@ -73,10 +102,148 @@ fn main() {
         .build()
         .expect("failed to create game context");
 
-    let gui_state = if let Some(connection) = connection {
-        GuiState::new_remote(&mut ctx, connection).expect("failed to setup GUI")
+    let mut gui_state = if let Some(connection) = connection {
+        GuiState::new_remote(&mut ctx, connection, &gui_options).expect("failed to setup GUI")
     } else {
-        GuiState::new_local(&mut ctx).expect("failed to setup GUI")
+        GuiState::new_local(&mut ctx, &gui_options).expect("failed to setup GUI")
     };
+    gui_state.replay_moves(saved_moves);
     ggez::event::run(ctx, event_loop, gui_state);
 }
+
+/// Run the protocol conformance harness against a peer, exiting with a non-zero status if any
+/// case did not pass
+fn run_conformance(address: &str, host: bool, options: &ConnectionOptions) {
+    let passphrase = options.passphrase.as_deref();
+    let (transport, local_player) = if host {
+        println!("Waiting for peer...");
+        let (transport, other_address) = network::transport::host(
+            options.transport, address, passphrase, options.frames)
+            .expect("failed to accept connection");
+        println!("Connected to {other_address}");
+        (transport, Color::Black)
+    } else {
+        println!("Connecting to {address}...");
+        let transport = network::transport::join(
+            options.transport, address, passphrase, options.frames)
+            .expect("failed to connect to remote host");
+        println!("Connected to {address}");
+        (transport, Color::White)
+    };
+    let harness = Harness::new(transport, local_player, Duration::from_secs(options.timeout),
+                               conformance::QUIET_PERIOD);
+    let report = harness.run();
+    print!("{report}");
+    if !report.all_passed() {
+        std::process::exit(1);
+    }
+}
+
+/// Forward frames between a host and a joiner, recording them to a file
+fn run_proxy(listen_address: &str, host_address: &str, record: &Path, options: &ConnectionOptions) {
+    let mut recording = std::fs::File::create(record).expect("failed to create recording");
+    let passphrase = options.passphrase.as_deref();
+    println!("Connecting to host {host_address}...");
+    let host = network::transport::join(options.transport, host_address, passphrase, options.frames)
+        .expect("failed to connect to host");
+    println!("Waiting for joining player...");
+    let (join, join_address) = network::transport::host(options.transport, listen_address, passphrase, options.frames)
+        .expect("failed to accept connection");
+    println!("Proxying between {host_address} and {join_address}");
+    network::proxy::run_proxy(host, join, &mut recording).expect("proxy session failed");
+}
+
+/// Play back a recording against a live client, exiting with a non-zero status if the client did
+/// not behave as recorded
+fn run_replay(recording: &Path, address: &str, join: bool, options: &ConnectionOptions) {
+    let frames = network::proxy::read_recording(recording).expect("failed to read recording");
+    let passphrase = options.passphrase.as_deref();
+    let (mut transport, replayed_side) = if join {
+        println!("Connecting to {address}...");
+        let transport = network::transport::join(options.transport, address, passphrase, options.frames)
+            .expect("failed to connect to remote host");
+        (transport, Role::Join)
+    } else {
+        println!("Waiting for client...");
+        let (transport, _) = network::transport::host(options.transport, address, passphrase, options.frames)
+            .expect("failed to accept connection");
+        (transport, Role::Host)
+    };
+    println!("Replaying {} frames", frames.len());
+    let mismatches = network::proxy::replay(
+        transport.as_mut(), &frames, replayed_side, Duration::from_secs(options.timeout));
+    let _ = transport.close();
+    match mismatches {
+        Ok(0) => println!("Client behaved as recorded"),
+        Ok(mismatches) => {
+            println!("{mismatches} frames differed from the recording");
+            std::process::exit(1);
+        },
+        Err(e) => {
+            println!("Connection failed: {e:?}");
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Print every frame in a capture, exiting with a non-zero status if any frame could not be decoded
+/// or violates the padding rules
+fn run_inspect(input: &Path, format: FrameFormat, frame_mode: FrameMode) {
+    let data = if input == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        std::fs::read(input)
+    }.expect("failed to read input");
+    let frames = network::inspect::read_frames(&data, format, frame_mode).unwrap_or_else(|e| {
+        eprintln!("Failed to read frames: {e}");
+        std::process::exit(2);
+    });
+
+    let mut failed = 0;
+    let mut padding_violations = 0;
+    for frame in &frames {
+        let inspection = network::inspect::inspect(frame, frame_mode);
+        println!("{}", inspection.text);
+        failed += !inspection.decoded as usize;
+        padding_violations += inspection.padding_violation as usize;
+    }
+    println!("{} frames, {failed} failed to decode, {padding_violations} with padding violations",
+             frames.len());
+    if failed > 0 || padding_violations > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Set up a connection with an established transport, along with its session log
+fn new_connection(transport: Box<dyn Transport>, local_player: Color, options: &ConnectionOptions,
+                  other_address: &str) -> GameConnection
+{
+    let session_log = open_session_log(
+        &options.log_dir, local_player, &format!("Connected to {other_address}"));
+    let mut connection = GameConnection::new(transport, local_player, options.strict,
+                                             Duration::from_secs(options.timeout), session_log);
+    connection.set_share_annotations(options.share_annotations);
+    if options.heartbeat {
+        connection.enable_heartbeat();
+    }
+    connection
+}
+
+/// Create a session log, continuing without one if it cannot be created
+///
+/// # Arguments
+///
+/// * `description`: How the session was started, written as the first event of the log
+fn open_session_log(log_dir: &Path, local_player: Color, description: &str) -> Option<SessionLog> {
+    match SessionLog::create(log_dir, local_player) {
+        Ok(mut session_log) => {
+            session_log.event(&format!("{description} as {local_player:?}"));
+            Some(session_log)
+        },
+        Err(e) => {
+            println!("Failed to create session log: {e}");
+            None
+        },
+    }
+}
@@ -1,37 +1,128 @@
 pub mod chess_tp;
+pub mod conformance;
+pub mod correspondence;
+pub mod heartbeat;
+pub mod inspect;
+pub mod proxy;
+pub mod session_log;
+pub mod transport;
 
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use rsoderh_chess::Color;
 use crate::network::chess_tp::Message;
+use crate::network::heartbeat::Heartbeat;
+use crate::network::session_log::SessionLog;
+use crate::network::transport::{Transport, TransportError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadError {
+    /// The underlying transport failed to receive a frame
+    Transport(TransportError),
+    /// A frame was received, but could not be decoded as a message
+    Decode,
+}
 
 #[derive(Debug)]
 pub struct GameConnection {
-    connection: TcpStream,
+    transport: Box<dyn Transport>,
     local_player: Color,
     strict_rule_policy: bool,
     has_quit: bool,
+    disconnected: bool,
+    /// Whether the game can be resumed later, as in correspondence games
+    resumable: bool,
+    /// Whether the local player's annotations are sent to the other player
+    share_annotations: bool,
+    heartbeat: Heartbeat,
+    received: VecDeque<Result<Message, ReadError>>,
+    session_log: Option<SessionLog>,
 }
 
 impl GameConnection {
-    pub fn new(connection: TcpStream, local_player: Color, strict_rule_policy: bool) -> GameConnection {
+    pub fn new(transport: Box<dyn Transport>, local_player: Color, strict_rule_policy: bool,
+               timeout: Duration, session_log: Option<SessionLog>) -> GameConnection
+    {
         GameConnection {
-            connection,
+            transport,
             local_player,
             strict_rule_policy,
             has_quit: false,
+            disconnected: false,
+            resumable: false,
+            share_annotations: false,
+            heartbeat: Heartbeat::new(timeout),
+            received: VecDeque::new(),
+            session_log,
         }
     }
 
     pub fn send_message(&mut self, message: Message) -> Result<(), ()> {
-        let message = message.encode()?;
-        self.connection.write(&message).map(|_| ()).map_err(|_| ())
+        let logged = !is_heartbeat(&message);
+        let frame = message.encode_frame(self.transport.frame_mode())?;
+        if let Some(session_log) = self.session_log.as_mut().filter(|_| logged) {
+            session_log.frame_sent(&frame);
+        }
+        self.transport.send_frame(&frame).map_err(|e| {
+            self.log_event(&format!("Failed to send frame: {e:?}"));
+        })
+    }
+
+    /// Send a ping if one is due and heartbeats are enabled, and receive all available frames.
+    /// Pings are answered immediately, while other messages are queued to be returned by
+    /// [read_message](GameConnection::read_message).
+    pub fn poll(&mut self) {
+        if self.disconnected || self.has_quit { return; }
+        let now = Instant::now();
+        if let Some(id) = self.heartbeat.poll_ping(now).filter(|_| !self.resumable) {
+            let _ = self.send_message(Message::ChessPing { id });
+        }
+        loop {
+            let frame = match self.transport.poll_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(TransportError::Disconnected) => {
+                    self.log_event("Connection closed");
+                    self.disconnected = true;
+                    break;
+                },
+                // the same error is likely to be returned again, so it is reported once per poll
+                Err(e) => {
+                    self.log_event(&format!("Failed to receive frame: {e:?}"));
+                    self.received.push_back(Err(ReadError::Transport(e)));
+                    break;
+                },
+            };
+            self.heartbeat.on_frame_received(now);
+            let message = Message::decode(&frame);
+            // heartbeats would drown out the rest of the log
+            let logged = !message.as_ref().is_ok_and(is_heartbeat);
+            if let Some(session_log) = self.session_log.as_mut().filter(|_| logged) {
+                session_log.frame_received(&frame);
+                session_log.decoded(&message);
+            }
+            match message {
+                // a peer that sends heartbeats can also decode ours
+                Ok(Message::ChessPing { id }) => {
+                    self.heartbeat.enable();
+                    let _ = self.send_message(Message::ChessPong { id });
+                },
+                Ok(Message::ChessPong { id }) => {
+                    self.heartbeat.enable();
+                    self.heartbeat.on_pong(id, now);
+                },
+                Ok(message) => self.received.push_back(Ok(message)),
+                Err(()) => self.received.push_back(Err(ReadError::Decode)),
+            }
+        }
     }
 
-    pub fn read_message(&mut self) -> Result<Message, ()> {
-        let mut buffer = [0u8; chess_tp::BUFFER_SIZE];
-        self.connection.read(&mut buffer).map_err(|_| ())?;
-        Message::decode(&buffer)
+    /// returns: The next message received from the other player, or `None` if no message has
+    ///          arrived yet
+    pub fn read_message(&mut self) -> Result<Option<Message>, ReadError> {
+        if self.has_quit { return Ok(None); }
+        self.poll();
+        self.received.pop_front().transpose()
     }
 
     pub fn local_player(&self) -> Color {
@@ -42,7 +133,186 @@ impl GameConnection {
         self.strict_rule_policy
     }
 
+    /// Mark the game as resumable, meaning that the other player is not expected to be present.
+    /// No heartbeats are sent, and closing the game does not quit it.
+    pub fn set_resumable(&mut self, resumable: bool) {
+        self.resumable = resumable;
+    }
+
+    pub fn is_resumable(&self) -> bool {
+        self.resumable
+    }
+
+    /// Send the local player's arrows and marked squares to the other player whenever they change.
+    /// Peers that do not support annotation messages will fail to decode them.
+    pub fn set_share_annotations(&mut self, share_annotations: bool) {
+        self.share_annotations = share_annotations;
+    }
+
+    pub fn shares_annotations(&self) -> bool {
+        self.share_annotations
+    }
+
+    /// Send heartbeats from the start of the game, rather than only once the other player has sent
+    /// one. Peers that do not support heartbeat messages will fail to decode them.
+    pub fn enable_heartbeat(&mut self) {
+        self.heartbeat.enable();
+    }
+
+    /// returns: The round-trip time of the latest heartbeat, if the other player answers them
+    pub fn latency(&self) -> Option<Duration> {
+        self.heartbeat.latency()
+    }
+
+    /// returns: Whether the connection was closed, or the other player has stopped responding
+    pub fn is_connection_lost(&self) -> bool {
+        self.disconnected || self.heartbeat.is_timed_out(Instant::now())
+    }
+
+    /// Restart the timeout after the connection has been considered lost
+    pub fn keep_waiting(&mut self) {
+        self.heartbeat.reset(Instant::now());
+    }
+
+    /// Write an entry to the session log, if there is one
+    pub fn log_event(&mut self, text: &str) {
+        if let Some(session_log) = &mut self.session_log {
+            session_log.event(text);
+        }
+    }
+
+    /// Close the connection without notifying the other player, so that a resumable game can be
+    /// continued later
+    pub fn close(&mut self) -> Result<(), ()> {
+        if self.has_quit { return Ok(()); }
+        self.has_quit = true;
+        self.log_event("Closed the game to resume it later");
+        self.transport.close().map_err(|_| ())
+    }
+
+    /// Notify the other player that we are quitting and close the connection. Does nothing if the
+    /// connection has already been closed.
     pub fn quit(&mut self, message: Option<String>) -> Result<(), ()> {
-        self.send_message(Message::ChessQuit { payload: message.unwrap_or(String::new()) })
+        if self.has_quit { return Ok(()); }
+        self.has_quit = true;
+        let payload = message.unwrap_or_default();
+        self.log_event(&format!("Quit: {payload}"));
+        let result = self.send_message(Message::ChessQuit { payload });
+        self.transport.close().map_err(|_| ()).and(result)
+    }
+}
+
+fn is_heartbeat(message: &Message) -> bool {
+    matches!(message, Message::ChessPing { .. } | Message::ChessPong { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::network::transport::{FrameMode, MemoryTransport};
+    use super::*;
+
+    /// [Transport] that fails to receive every frame
+    #[derive(Debug)]
+    struct FailingTransport;
+
+    impl Transport for FailingTransport {
+        fn send_frame(&mut self, _frame: &[u8]) -> Result<(), TransportError> {
+            Ok(())
+        }
+
+        fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+            Err(TransportError::Unauthenticated)
+        }
+
+        fn close(&mut self) -> Result<(), TransportError> {
+            Ok(())
+        }
+
+        fn frame_mode(&self) -> FrameMode {
+            FrameMode::Fixed
+        }
+
+        fn set_frame_mode(&mut self, _frame_mode: FrameMode) {}
+    }
+
+    fn connection(transport: impl Transport + 'static, local_player: Color) -> GameConnection {
+        connection_with_timeout(transport, local_player, Duration::from_secs(30))
+    }
+
+    fn connection_with_timeout(transport: impl Transport + 'static, local_player: Color,
+                               timeout: Duration) -> GameConnection
+    {
+        GameConnection::new(Box::new(transport), local_player, false, timeout, None)
+    }
+
+    fn pair() -> (GameConnection, GameConnection) {
+        let (white, black) = MemoryTransport::pair();
+        (connection(white, Color::White), connection(black, Color::Black))
+    }
+
+    #[test]
+    fn messages_are_passed_to_the_other_player() {
+        let (mut white, mut black) = pair();
+        assert_eq!(black.read_message(), Ok(None));
+        white.send_message(Message::ChessQuit { payload: "bye".to_owned() }).unwrap();
+        assert_eq!(black.read_message(),
+                   Ok(Some(Message::ChessQuit { payload: "bye".to_owned() })));
+        assert_eq!(black.read_message(), Ok(None));
+    }
+
+    #[test]
+    fn undecodable_frames_are_reported() {
+        let (mut white, black) = MemoryTransport::pair();
+        let mut black = connection(black, Color::Black);
+        white.send_frame(&[b'x'; chess_tp::BUFFER_SIZE]).unwrap();
+        assert_eq!(black.read_message(), Err(ReadError::Decode));
+        assert_eq!(black.read_message(), Ok(None));
+    }
+
+    #[test]
+    fn transport_errors_are_reported_once_per_poll() {
+        let mut connection = connection(FailingTransport, Color::White);
+        connection.poll();
+        assert_eq!(connection.received.len(), 1);
+        assert_eq!(connection.read_message(),
+                   Err(ReadError::Transport(TransportError::Unauthenticated)));
+    }
+
+    #[test]
+    fn closed_transports_are_lost_connections() {
+        let (mut white, mut black) = pair();
+        white.quit(None).unwrap();
+        assert!(!black.is_connection_lost());
+        assert_eq!(black.read_message(), Ok(Some(Message::ChessQuit { payload: String::new() })));
+        assert!(black.is_connection_lost());
+        assert_eq!(black.read_message(), Ok(None));
+    }
+
+    #[test]
+    fn pings_are_answered_with_pongs() {
+        let (mut white, mut black) = pair();
+        white.enable_heartbeat();
+        white.poll();
+        black.poll();
+        white.poll();
+        assert!(white.latency().is_some());
+        // the pong shows that white supports heartbeats too
+        assert!(black.heartbeat.is_enabled());
+        assert_eq!(white.read_message(), Ok(None));
+    }
+
+    #[test]
+    fn silent_opponents_are_lost_connections() {
+        let (white, _black) = MemoryTransport::pair();
+        let mut white = connection_with_timeout(white, Color::White, Duration::from_millis(20));
+        white.enable_heartbeat();
+        white.poll();
+        assert!(!white.is_connection_lost());
+        thread::sleep(Duration::from_millis(50));
+        white.poll();
+        assert!(white.is_connection_lost());
+        white.keep_waiting();
+        assert!(!white.is_connection_lost());
     }
 }
@@ -1,10 +1,17 @@
-mod util;
+pub mod util;
 
+use std::fmt::{Debug, Formatter};
 use rsoderh_chess::{Board, Color, HalfMoveRequest, PieceKind, Position};
+use crate::network::transport::FrameMode;
 
 pub const BUFFER_SIZE: usize = 128;
+/// The largest message that can be encoded when using [FrameMode::Variable]
+pub const MAX_VARIABLE_MESSAGE_SIZE: usize = 4096;
 const CHESS_MOVE_IDENTIFIER: &[u8] = b"ChessMOVE";
 const CHESS_QUIT_IDENTIFIER: &[u8] = b"ChessQUIT";
+const CHESS_PING_IDENTIFIER: &[u8] = b"ChessPING";
+const CHESS_PONG_IDENTIFIER: &[u8] = b"ChessPONG";
+const CHESS_ANNOTATE_IDENTIFIER: &[u8] = b"ChessANNO";
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GameStateType {
@@ -14,6 +21,21 @@ pub enum GameStateType {
     Draw,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationColor {
+    Green,
+    Red,
+    Blue,
+    Yellow,
+}
+
+/// A marking drawn on the board by a player, which has no effect on the game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Annotation {
+    Arrow { source: Position, dest: Position, color: AnnotationColor },
+    Mark { square: Position, color: AnnotationColor },
+}
+
 #[derive(Clone, Debug)]
 struct MessageBuilder<const N: usize> {
     buffer: [u8; N],
@@ -40,18 +62,18 @@ impl<const N: usize> MessageBuilder<N> {
 
     pub fn write_slice(mut self, section: &[u8]) -> Result<Self, ()> {
         if self.index + section.len() > N { return Err(()); }
-        &self.buffer[self.index..(self.index + section.len())].copy_from_slice(section);
+        self.buffer[self.index..(self.index + section.len())].copy_from_slice(section);
         self.index += section.len();
         Ok(self)
     }
 
-    pub fn write_pos(mut self, pos: Position) -> Result<Self, ()> {
+    pub fn write_pos(self, pos: Position) -> Result<Self, ()> {
         let file = b'A' + pos.column.get();
         let rank = b'1' + pos.row.get();
         self.write_slice(&[file, rank])
     }
 
-    pub fn write_promotion_type(mut self, promotion_type: Option<PieceKind>) -> Result<Self, ()> {
+    pub fn write_promotion_type(self, promotion_type: Option<PieceKind>) -> Result<Self, ()> {
         match promotion_type {
             None => self.skip(1),
             Some(promotion_type) => {
@@ -68,7 +90,7 @@ impl<const N: usize> MessageBuilder<N> {
         }
     }
 
-    pub fn write_game_state(mut self, game_state: GameStateType) -> Result<Self, ()> {
+    pub fn write_game_state(self, game_state: GameStateType) -> Result<Self, ()> {
         let state = match game_state {
             GameStateType::Normal => b"0-0",
             GameStateType::WhiteWon => b"1-0",
@@ -78,13 +100,35 @@ impl<const N: usize> MessageBuilder<N> {
         self.write_slice(state)
     }
 
-    pub fn write_board(mut self, board: &Board) -> Result<Self, ()> {
+    pub fn write_board(self, board: &Board) -> Result<Self, ()> {
         self.write_slice(&util::board_to_fen(board))
     }
 
+    pub fn write_annotations(mut self, annotations: &[Annotation]) -> Result<Self, ()> {
+        for (index, annotation) in annotations.iter().enumerate() {
+            if index > 0 {
+                self = self.write(b',')?;
+            }
+            self = match *annotation {
+                Annotation::Arrow { source, dest, color } => {
+                    self.write(annotation_color_to_char(color))?.write_pos(source)?.write_pos(dest)?
+                },
+                Annotation::Mark { square, color } => {
+                    self.write(annotation_color_to_char(color))?.write_pos(square)?
+                },
+            };
+        }
+        Ok(self)
+    }
+
     pub fn build(self) -> [u8; N] {
         self.buffer
     }
+
+    /// returns: The written part of the buffer, without any trailing padding
+    pub fn build_trimmed(self) -> Vec<u8> {
+        self.buffer[..self.index].to_vec()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -104,7 +148,7 @@ impl<'a> MessageReader<'a> {
     }
 
     pub fn read(&mut self) -> Result<u8, ()> {
-        if self.buffer.len() < 1 { return Err(()); }
+        if self.buffer.is_empty() { return Err(()); }
         let value = self.buffer[0];
         self.buffer = &self.buffer[1..];
         Ok(value)
@@ -165,15 +209,106 @@ impl<'a> MessageReader<'a> {
         let fen = self.read_up_to(b':')?;
         util::board_from_fen(fen).ok_or(())
     }
+
+    /// Read a comma-separated list of annotations, each a color followed by the square it marks or
+    /// the two squares of an arrow
+    pub fn read_annotations_argument(&mut self) -> Result<Vec<Annotation>, ()> {
+        let list = self.read_up_to(b':')?;
+        if list.is_empty() { return Ok(Vec::new()); }
+        list.split(|b| *b == b',')
+            .map(|entry| {
+                let mut reader = MessageReader::new(entry);
+                let color = annotation_color_from_char(reader.read()?).ok_or(())?;
+                let square = reader.read_pos()?;
+                if reader.buffer.is_empty() {
+                    return Ok(Annotation::Mark { square, color });
+                }
+                let dest = reader.read_pos()?;
+                if !reader.buffer.is_empty() { return Err(()); }
+                Ok(Annotation::Arrow { source: square, dest, color })
+            })
+            .collect()
+    }
+
+    pub fn read_number_argument(&mut self) -> Result<u32, ()> {
+        let digits = self.read_up_to(b':')?;
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) { return Err(()); }
+        str::from_utf8(digits).map_err(|_| ())?.parse().map_err(|_| ())
+    }
 }
 
+// the variants are named after the message types of the protocol
+#[allow(clippy::enum_variant_names)]
 pub enum Message {
     ChessMove { player: Option<Color>, chess_move: HalfMoveRequest, new_game_state: GameStateType, new_board: Board },
     ChessQuit { payload: String },
+    /// Heartbeat sent periodically, which should be answered with a [ChessPong](Message::ChessPong)
+    /// with the same id
+    ChessPing { id: u32 },
+    ChessPong { id: u32 },
+    /// Every annotation currently drawn by the sender, replacing any previously sent ones
+    ChessAnnotate { annotations: Vec<Annotation> },
+}
+
+impl Debug for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Message::ChessMove { player, chess_move, new_game_state, new_board } => {
+                let chess_move: &dyn Debug = match chess_move {
+                    HalfMoveRequest::Standard { source, dest } => &(source, dest),
+                    HalfMoveRequest::Promotion { column, kind } => &(column.get(), kind),
+                };
+                f.debug_struct("ChessMove")
+                    .field("player", player)
+                    .field("chess_move", chess_move)
+                    .field("new_game_state", new_game_state)
+                    .field("new_board", new_board)
+                    .finish()
+            },
+            Message::ChessQuit { payload } => {
+                f.debug_struct("ChessQuit").field("payload", payload).finish()
+            },
+            Message::ChessPing { id } => f.debug_struct("ChessPing").field("id", id).finish(),
+            Message::ChessPong { id } => f.debug_struct("ChessPong").field("id", id).finish(),
+            Message::ChessAnnotate { annotations } => {
+                f.debug_struct("ChessAnnotate").field("annotations", annotations).finish()
+            },
+        }
+    }
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Message::ChessMove { player: a_player, chess_move: a_move, new_game_state: a_state, new_board: a_board },
+                Message::ChessMove { player: b_player, chess_move: b_move, new_game_state: b_state, new_board: b_board }) => {
+                a_player == b_player && moves_equal(a_move, b_move) && a_state == b_state
+                    && a_board == b_board
+            },
+            (Message::ChessQuit { payload: a }, Message::ChessQuit { payload: b }) => a == b,
+            (Message::ChessPing { id: a }, Message::ChessPing { id: b }) => a == b,
+            (Message::ChessPong { id: a }, Message::ChessPong { id: b }) => a == b,
+            (Message::ChessAnnotate { annotations: a }, Message::ChessAnnotate { annotations: b }) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl Message {
     pub fn encode(self) -> Result<[u8; BUFFER_SIZE], ()> {
+        Ok(self.into_builder::<BUFFER_SIZE>()?.build())
+    }
+
+    /// Encode the message as a frame for the given framing. Variable-length frames are not padded,
+    /// and may be up to [MAX_VARIABLE_MESSAGE_SIZE] bytes long.
+    pub fn encode_frame(self, frame_mode: FrameMode) -> Result<Vec<u8>, ()> {
+        match frame_mode {
+            FrameMode::Fixed => self.encode().map(|frame| frame.to_vec()),
+            FrameMode::Variable => Ok(self.into_builder::<MAX_VARIABLE_MESSAGE_SIZE>()?.build_trimmed()),
+        }
+    }
+
+    fn into_builder<const N: usize>(self) -> Result<MessageBuilder<N>, ()> {
         match self {
             Message::ChessMove { player, chess_move, new_game_state, new_board } => {
                 encode_move(player.ok_or(())?, chess_move, new_game_state, &new_board)
@@ -181,6 +316,15 @@ impl Message {
             Message::ChessQuit { payload } => {
                 encode_quit(&payload)
             }
+            Message::ChessPing { id } => {
+                encode_heartbeat(CHESS_PING_IDENTIFIER, id)
+            }
+            Message::ChessPong { id } => {
+                encode_heartbeat(CHESS_PONG_IDENTIFIER, id)
+            }
+            Message::ChessAnnotate { annotations } => {
+                encode_annotate(&annotations)
+            }
         }
     }
 
@@ -191,13 +335,64 @@ impl Message {
         match message_identifier {
             CHESS_MOVE_IDENTIFIER => decode_move(reader),
             CHESS_QUIT_IDENTIFIER => decode_quit(reader),
+            CHESS_PING_IDENTIFIER => decode_heartbeat(reader).map(|id| Message::ChessPing { id }),
+            CHESS_PONG_IDENTIFIER => decode_heartbeat(reader).map(|id| Message::ChessPong { id }),
+            CHESS_ANNOTATE_IDENTIFIER => decode_annotate(reader),
             _ => Err(()),
         }
     }
 }
 
-fn encode_move(player: Color, chess_move: HalfMoveRequest, new_game_state: GameStateType,
-               new_board: &Board) -> Result<[u8; BUFFER_SIZE], ()>
+/// Locate the padding of a frame, which follows the separator after the last argument of the
+/// message
+///
+/// returns: The index of the first padding byte, or `None` if the frame does not start with a known
+///          message identifier or is missing separators
+pub fn padding_start(message: &[u8]) -> Option<usize> {
+    let mut reader = MessageReader::new(message);
+    let separators = match reader.read_up_to(b':').ok()? {
+        CHESS_MOVE_IDENTIFIER => 4,
+        CHESS_QUIT_IDENTIFIER | CHESS_PING_IDENTIFIER | CHESS_PONG_IDENTIFIER
+            | CHESS_ANNOTATE_IDENTIFIER => 2,
+        _ => return None,
+    };
+    message.iter().enumerate()
+        .filter(|(_, byte)| **byte == b':')
+        .nth(separators - 1)
+        .map(|(index, _)| index + 1)
+}
+
+/// returns: A copy of the move
+pub fn clone_move(chess_move: &HalfMoveRequest) -> HalfMoveRequest {
+    match chess_move {
+        HalfMoveRequest::Standard { source, dest } => {
+            HalfMoveRequest::Standard { source: *source, dest: *dest }
+        },
+        HalfMoveRequest::Promotion { column, kind } => {
+            HalfMoveRequest::Promotion { column: *column, kind: *kind }
+        },
+    }
+}
+
+/// returns: Whether both moves have the same squares, or promote the same pawn to the same piece
+pub fn moves_equal(a: &HalfMoveRequest, b: &HalfMoveRequest) -> bool {
+    match (a, b) {
+        (HalfMoveRequest::Standard { source: a_source, dest: a_dest },
+            HalfMoveRequest::Standard { source: b_source, dest: b_dest }) => {
+            a_source == b_source && a_dest == b_dest
+        },
+        (HalfMoveRequest::Promotion { column: a_column, kind: a_kind },
+            HalfMoveRequest::Promotion { column: b_column, kind: b_kind }) => {
+            a_column.get() == b_column.get()
+                && std::mem::discriminant(a_kind) == std::mem::discriminant(b_kind)
+        },
+        _ => false,
+    }
+}
+
+fn encode_move<const N: usize>(player: Color, chess_move: HalfMoveRequest,
+                               new_game_state: GameStateType,
+                               new_board: &Board) -> Result<MessageBuilder<N>, ()>
 {
     let (source_pos, dest_pos, promotion_type) = match chess_move {
         HalfMoveRequest::Standard { source, dest } => (source, dest, None),
@@ -212,7 +407,7 @@ fn encode_move(player: Color, chess_move: HalfMoveRequest, new_game_state: GameS
         }
     };
 
-    let message = MessageBuilder::<BUFFER_SIZE>::new(Some(b'0'))?
+    let message = MessageBuilder::<N>::new(Some(b'0'))?
         .write_slice(CHESS_MOVE_IDENTIFIER)?
         .write(b':')?
         .write_pos(source_pos)?
@@ -224,17 +419,56 @@ fn encode_move(player: Color, chess_move: HalfMoveRequest, new_game_state: GameS
         .write_board(new_board)?
         .write(b':')?;
 
-    Ok(message.build())
+    Ok(message)
 }
 
-fn encode_quit(payload: &str) -> Result<[u8; BUFFER_SIZE], ()> {
-    let message = MessageBuilder::<BUFFER_SIZE>::new(Some(b'0'))?
+fn encode_quit<const N: usize>(payload: &str) -> Result<MessageBuilder<N>, ()> {
+    let message = MessageBuilder::<N>::new(Some(b'0'))?
         .write_slice(CHESS_QUIT_IDENTIFIER)?
         .write(b':')?
         .write_slice(payload.as_bytes())?
         .write(b':')?;
 
-    Ok(message.build())
+    Ok(message)
+}
+
+fn encode_heartbeat<const N: usize>(identifier: &[u8], id: u32) -> Result<MessageBuilder<N>, ()> {
+    let message = MessageBuilder::<N>::new(Some(b'0'))?
+        .write_slice(identifier)?
+        .write(b':')?
+        .write_slice(id.to_string().as_bytes())?
+        .write(b':')?;
+
+    Ok(message)
+}
+
+fn encode_annotate<const N: usize>(annotations: &[Annotation]) -> Result<MessageBuilder<N>, ()> {
+    let message = MessageBuilder::<N>::new(Some(b'0'))?
+        .write_slice(CHESS_ANNOTATE_IDENTIFIER)?
+        .write(b':')?
+        .write_annotations(annotations)?
+        .write(b':')?;
+
+    Ok(message)
+}
+
+fn annotation_color_to_char(color: AnnotationColor) -> u8 {
+    match color {
+        AnnotationColor::Green => b'g',
+        AnnotationColor::Red => b'r',
+        AnnotationColor::Blue => b'b',
+        AnnotationColor::Yellow => b'y',
+    }
+}
+
+fn annotation_color_from_char(ch: u8) -> Option<AnnotationColor> {
+    match ch {
+        b'g' => Some(AnnotationColor::Green),
+        b'r' => Some(AnnotationColor::Red),
+        b'b' => Some(AnnotationColor::Blue),
+        b'y' => Some(AnnotationColor::Yellow),
+        _ => None,
+    }
 }
 
 fn decode_move(mut reader: MessageReader) -> Result<Message, ()> {
@@ -271,3 +505,252 @@ fn decode_quit(mut reader: MessageReader) -> Result<Message, ()> {
     reader.check_rest(b'0')?;
     Ok(Message::ChessQuit { payload: payload.to_owned() })
 }
+
+fn decode_annotate(mut reader: MessageReader) -> Result<Message, ()> {
+    let annotations = reader.read_annotations_argument()?;
+    reader.skip(1)?;
+    reader.check_rest(b'0')?;
+    Ok(Message::ChessAnnotate { annotations })
+}
+
+fn decode_heartbeat(mut reader: MessageReader) -> Result<u32, ()> {
+    let id = reader.read_number_argument()?;
+    reader.skip(1)?;
+    reader.check_rest(b'0')?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use rsoderh_chess::{Game, MoveResult, Piece, Slot};
+    use super::*;
+    use super::util::{board_from_fen, board_to_fen};
+
+    const PIECE_KINDS: [PieceKind; 6] = [
+        PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop,
+        PieceKind::Rook, PieceKind::Queen, PieceKind::King,
+    ];
+    const PROMOTION_KINDS: [PieceKind; 4] =
+        [PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen];
+    const GAME_STATES: [GameStateType; 4] = [
+        GameStateType::Normal, GameStateType::WhiteWon, GameStateType::BlackWon, GameStateType::Draw,
+    ];
+
+    fn color(white: bool) -> Color {
+        if white { Color::White } else { Color::Black }
+    }
+
+    fn position() -> impl Strategy<Value = Position> {
+        (0..8u8, 0..8u8).prop_map(|(column, row)| Position::new(column, row).unwrap())
+    }
+
+    /// Any arrangement of pieces, legal or not
+    fn board() -> impl Strategy<Value = Board> {
+        prop::collection::vec(prop::option::of((0..PIECE_KINDS.len(), any::<bool>())), 64)
+            .prop_map(|slots| {
+                let mut board = Board::new_empty();
+                for (index, slot) in slots.into_iter().enumerate() {
+                    if let Some((kind, white)) = slot {
+                        let pos = Position::new(index as u8 % 8, index as u8 / 8).unwrap();
+                        let piece = Piece { kind: PIECE_KINDS[kind], color: color(white) };
+                        *board.at_position_mut(pos) = Slot::Occupied(piece);
+                    }
+                }
+                board
+            })
+    }
+
+    /// Play a game of random legal moves from the standard starting position, choosing each move
+    /// by the next of `choices`
+    ///
+    /// returns: The last move made, encoded as a `ChessMove` message
+    fn play_random_game(choices: &[u16]) -> Message {
+        let mut game = Game::new_standard();
+        let mut last_move = None;
+        for choice in choices {
+            let player = game.turn;
+            let mut moves = Vec::new();
+            for index in 0..64 {
+                let source = Position::new(index % 8, index / 8).unwrap();
+                let is_own_piece = game.board().at_position(source).as_piece()
+                    .is_some_and(|piece| piece.color == player);
+                if !is_own_piece { continue; }
+                let is_pawn = matches!(game.board().at_position(source),
+                    Slot::Occupied(Piece { kind: PieceKind::Pawn, .. }));
+                for dest in game.valid_moves(source).unwrap_or_default() {
+                    moves.push((source, dest, is_pawn && (dest.row.get() == 0 || dest.row.get() == 7)));
+                }
+            }
+            if moves.is_empty() { break; }
+            let (source, dest, is_promotion) = moves[*choice as usize % moves.len()];
+            let to_request = || if is_promotion {
+                let kind = PROMOTION_KINDS[*choice as usize % PROMOTION_KINDS.len()];
+                HalfMoveRequest::Promotion { column: dest.column, kind }
+            } else {
+                HalfMoveRequest::Standard { source, dest }
+            };
+            let (next, new_game_state, new_board) = match game.perform_move(to_request()) {
+                MoveResult::Ongoing(game, ..) => {
+                    let board = game.board().clone();
+                    (Some(game), GameStateType::Normal, board)
+                },
+                MoveResult::Finished(game) => {
+                    let state = match game.result() {
+                        rsoderh_chess::GameResult::Checkmate { winner: Color::White, .. } =>
+                            GameStateType::WhiteWon,
+                        rsoderh_chess::GameResult::Checkmate { winner: Color::Black, .. } =>
+                            GameStateType::BlackWon,
+                    };
+                    (None, state, game.board().clone())
+                },
+                MoveResult::Illegal(..) => panic!("valid move was rejected as illegal"),
+            };
+            last_move = Some(Message::ChessMove {
+                player: Some(player),
+                chess_move: to_request(),
+                new_game_state,
+                new_board,
+            });
+            match next {
+                Some(next) => game = next,
+                None => break,
+            }
+        }
+        last_move.expect("the starting position has legal moves")
+    }
+
+    /// Any well-formed `ChessMove` message, including ones describing illegal moves and positions
+    fn arbitrary_move_message() -> impl Strategy<Value = Message> {
+        let promotion_kind = prop::option::of(0..PROMOTION_KINDS.len());
+        (any::<bool>(), position(), position(), promotion_kind, 0..GAME_STATES.len(), board())
+            .prop_map(|(white, source, dest, promotion_kind, state, new_board)| {
+                let chess_move = match promotion_kind {
+                    Some(kind) => {
+                        HalfMoveRequest::Promotion { column: source.column, kind: PROMOTION_KINDS[kind] }
+                    },
+                    None => HalfMoveRequest::Standard { source, dest },
+                };
+                Message::ChessMove {
+                    player: Some(color(white)),
+                    chess_move,
+                    new_game_state: GAME_STATES[state],
+                    new_board,
+                }
+            })
+    }
+
+    const ANNOTATION_COLORS: [AnnotationColor; 4] =
+        [AnnotationColor::Green, AnnotationColor::Red, AnnotationColor::Blue, AnnotationColor::Yellow];
+
+    /// Any list of annotations that fits in a fixed-size frame
+    fn annotations() -> impl Strategy<Value = Vec<Annotation>> {
+        let annotation = (0..ANNOTATION_COLORS.len(), position(), prop::option::of(position()))
+            .prop_map(|(color, square, dest)| {
+                let color = ANNOTATION_COLORS[color];
+                match dest {
+                    Some(dest) => Annotation::Arrow { source: square, dest, color },
+                    None => Annotation::Mark { square, color },
+                }
+            });
+        prop::collection::vec(annotation, 0..=19)
+    }
+
+    fn message() -> impl Strategy<Value = Message> {
+        prop_oneof![
+            prop::collection::vec(any::<u16>(), 1..80).prop_map(|choices| play_random_game(&choices)),
+            arbitrary_move_message(),
+            "[ -9;-~]{0,117}".prop_map(|payload| Message::ChessQuit { payload }),
+            any::<u32>().prop_map(|id| Message::ChessPing { id }),
+            any::<u32>().prop_map(|id| Message::ChessPong { id }),
+            annotations().prop_map(|annotations| Message::ChessAnnotate { annotations }),
+        ]
+    }
+
+    /// The decoded form of a message, which has no player since it is implied by the turn
+    fn without_player(message: Message) -> Message {
+        match message {
+            Message::ChessMove { chess_move, new_game_state, new_board, .. } => {
+                Message::ChessMove { player: None, chess_move, new_game_state, new_board }
+            },
+            message => message,
+        }
+    }
+
+    fn clone_message(message: &Message) -> Message {
+        match message {
+            Message::ChessMove { player, chess_move, new_game_state, new_board } => {
+                Message::ChessMove {
+                    player: *player,
+                    chess_move: clone_move(chess_move),
+                    new_game_state: *new_game_state,
+                    new_board: new_board.clone(),
+                }
+            },
+            Message::ChessQuit { payload } => Message::ChessQuit { payload: payload.clone() },
+            Message::ChessPing { id } => Message::ChessPing { id: *id },
+            Message::ChessPong { id } => Message::ChessPong { id: *id },
+            Message::ChessAnnotate { annotations } => {
+                Message::ChessAnnotate { annotations: annotations.clone() }
+            },
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn message_round_trips(message in message()) {
+            let frame = clone_message(&message).encode().unwrap();
+            prop_assert_eq!(Message::decode(&frame), Ok(without_player(message)));
+        }
+
+        #[test]
+        fn variable_frame_round_trips(message in message()) {
+            let frame = clone_message(&message).encode_frame(FrameMode::Variable).unwrap();
+            prop_assert_ne!(frame.last(), Some(&b'0'));
+            prop_assert_eq!(Message::decode(&frame), Ok(without_player(message)));
+        }
+
+        #[test]
+        fn long_quit_payload_needs_variable_frame(payload in "[ -9;-~]{118,1000}") {
+            let message = Message::ChessQuit { payload: payload.clone() };
+            prop_assert!(clone_message(&message).encode().is_err());
+            let frame = message.encode_frame(FrameMode::Variable).unwrap();
+            prop_assert_eq!(Message::decode(&frame), Ok(Message::ChessQuit { payload }));
+        }
+
+        #[test]
+        fn move_without_player_is_not_encoded(message in arbitrary_move_message()) {
+            prop_assert!(without_player(message).encode().is_err());
+        }
+
+        #[test]
+        fn fen_round_trips(board in board()) {
+            prop_assert_eq!(board_from_fen(&board_to_fen(&board)), Some(board));
+        }
+
+        #[test]
+        fn decode_never_panics(frame in prop::collection::vec(any::<u8>(), 0..=2 * BUFFER_SIZE)) {
+            let _ = Message::decode(&frame);
+        }
+
+        #[test]
+        fn decode_mutated_frame_never_panics(message in message(),
+                                             mutations in prop::collection::vec((0..BUFFER_SIZE, any::<u8>()), 1..8),
+                                             len in 0..=BUFFER_SIZE)
+        {
+            let mut frame = message.encode().unwrap();
+            for (index, byte) in mutations {
+                frame[index] = byte;
+            }
+            let _ = Message::decode(&frame[..len]);
+            let _ = Message::decode(&frame);
+        }
+
+        #[test]
+        fn board_from_fen_never_panics(fen in prop::collection::vec(
+            prop::sample::select(b"pnbrqkPNBRQK0123456789/:x".to_vec()), 0..100))
+        {
+            let _ = board_from_fen(&fen);
+        }
+    }
+}
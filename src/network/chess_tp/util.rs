@@ -1,8 +1,6 @@
-use std::vec;
-
 use rsoderh_chess::{Board, Color, Piece, PieceKind, Position, Slot};
 
-fn piece_to_char(piece: Piece) -> u8 {
+pub fn piece_to_char(piece: Piece) -> u8 {
     match (piece.color, piece.kind) {
         (Color::White, PieceKind::Pawn) => b'P',
         (Color::White, PieceKind::Knight) => b'N',
@@ -82,10 +80,10 @@ pub fn board_from_fen(fen: &[u8]) -> Option<Board> {
             file += 1;
         } else if (b'1'..=b'8').contains(byte) {
             let digit = byte - b'1' + 1;
-            if digit as u8 + file > 8 {
+            if digit + file > 8 {
                 return None;
             }
-            file += digit as u8;
+            file += digit;
         } else if *byte == b'/' {
             if file != 8 || rank > 6 {
                 return None;
@@ -0,0 +1,484 @@
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use rsoderh_chess::{Board, Color, Game, HalfMoveRequest, MoveResult, PieceKind, Position};
+use crate::network::chess_tp::{moves_equal, GameStateType, Message, BUFFER_SIZE};
+use crate::network::transport::{wait_for_frame, Transport};
+
+/// How long to wait for a reaction after sending a frame that the peer should ignore
+pub const QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+/// A behavior of the peer that is checked by the conformance harness
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Case {
+    NormalMoves,
+    Castling,
+    Promotion,
+    Checkmate,
+    MalformedFrame,
+    UnknownMessage,
+    OutOfRangeSquare,
+    BadFen,
+    BadPadding,
+}
+
+impl Case {
+    pub const ALL: [Case; 9] = [
+        Case::NormalMoves, Case::Castling, Case::Promotion, Case::Checkmate, Case::MalformedFrame,
+        Case::UnknownMessage, Case::OutOfRangeSquare, Case::BadFen, Case::BadPadding,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Case::NormalMoves => "normal moves",
+            Case::Castling => "castling",
+            Case::Promotion => "promotion",
+            Case::Checkmate => "checkmate",
+            Case::MalformedFrame => "malformed frame is ignored",
+            Case::UnknownMessage => "unknown message is ignored",
+            Case::OutOfRangeSquare => "out-of-range square is ignored",
+            Case::BadFen => "bad FEN is ignored",
+            Case::BadPadding => "bad padding is ignored",
+        }
+    }
+}
+
+/// The scripted game played against the peer, in UCI notation. White castles, promotes and
+/// delivers checkmate, so both directions are covered by hosting and joining.
+pub const SCRIPT: &[(&str, Case)] = &[
+    ("b2b4", Case::NormalMoves), ("a7a5", Case::NormalMoves),
+    ("b4a5", Case::NormalMoves), ("b8c6", Case::NormalMoves),
+    ("a5a6", Case::NormalMoves), ("a8b8", Case::NormalMoves),
+    ("a6a7", Case::NormalMoves), ("e7e5", Case::NormalMoves),
+    ("a7a8q", Case::Promotion), ("d7d6", Case::NormalMoves),
+    ("e2e3", Case::NormalMoves), ("h7h6", Case::NormalMoves),
+    ("f1c4", Case::NormalMoves), ("b7b6", Case::NormalMoves),
+    ("g1h3", Case::NormalMoves), ("c8d7", Case::NormalMoves),
+    ("e1g1", Case::Castling), ("g8e7", Case::NormalMoves),
+    ("d1h5", Case::NormalMoves), ("h8h7", Case::NormalMoves),
+    ("h5f7", Case::Checkmate),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    NotRun,
+}
+
+/// The outcome of every [Case], in the order of [Case::ALL]
+#[derive(Clone, Debug)]
+pub struct Report {
+    outcomes: Vec<(Case, Outcome)>,
+}
+
+impl Report {
+    fn new() -> Report {
+        Report { outcomes: Case::ALL.iter().map(|case| (*case, Outcome::NotRun)).collect() }
+    }
+
+    fn outcome_mut(&mut self, case: Case) -> &mut Outcome {
+        &mut self.outcomes.iter_mut().find(|(c, _)| *c == case).unwrap().1
+    }
+
+    /// Mark the case as passed, unless it has already failed
+    fn pass(&mut self, case: Case) {
+        let outcome = self.outcome_mut(case);
+        if *outcome == Outcome::NotRun {
+            *outcome = Outcome::Passed;
+        }
+    }
+
+    /// Mark the case as failed, keeping the reason of the first failure
+    fn fail(&mut self, case: Case, reason: String) {
+        let outcome = self.outcome_mut(case);
+        if !matches!(outcome, Outcome::Failed(_)) {
+            *outcome = Outcome::Failed(reason);
+        }
+    }
+
+    pub fn outcome(&self, case: Case) -> &Outcome {
+        &self.outcomes.iter().find(|(c, _)| *c == case).unwrap().1
+    }
+
+    /// returns: Whether every case was run and passed
+    pub fn all_passed(&self) -> bool {
+        self.outcomes.iter().all(|(_, outcome)| *outcome == Outcome::Passed)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Conformance report:")?;
+        for (case, outcome) in &self.outcomes {
+            match outcome {
+                Outcome::Passed => writeln!(f, "  PASS  {}", case.description())?,
+                Outcome::Failed(reason) => writeln!(f, "  FAIL  {}: {reason}", case.description())?,
+                Outcome::NotRun => writeln!(f, "  ----  {} (not run)", case.description())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Drives a peer implementation through the scripted game, sending the malformed frames at the
+/// harness's first turn
+pub struct Harness {
+    transport: Box<dyn Transport>,
+    local_player: Color,
+    move_timeout: Duration,
+    quiet_period: Duration,
+    report: Report,
+}
+
+impl Harness {
+    /// # Arguments
+    ///
+    /// * `local_player`: The color played by the harness
+    /// * `move_timeout`: How long to wait for the peer to make each of its moves
+    /// * `quiet_period`: How long to wait for a reaction to frames the peer should ignore
+    pub fn new(transport: Box<dyn Transport>, local_player: Color, move_timeout: Duration,
+               quiet_period: Duration) -> Harness
+    {
+        Harness { transport, local_player, move_timeout, quiet_period, report: Report::new() }
+    }
+
+    /// Play the scripted game against the peer. The run stops at the first failure that makes the
+    /// remaining script meaningless, leaving later cases as not run.
+    pub fn run(mut self) -> Report {
+        let _ = self.play_script();
+        let _ = self.transport.close();
+        self.report
+    }
+
+    fn play_script(&mut self) -> Result<(), ()> {
+        let mut game = Some(Game::new_standard());
+        // case of our last move, which passes once the peer answers with a matching board
+        let mut pending_case = None;
+        let mut sent_frame_cases = false;
+        for (index, (uci, case)) in SCRIPT.iter().enumerate() {
+            let current = game.take().ok_or(())?;
+            let player = current.turn;
+            let (next, new_game_state, new_board) = perform_scripted_move(current, uci)
+                .ok_or_else(|| self.report.fail(*case, format!("scripted move {uci} is illegal")))?;
+
+            if player == self.local_player {
+                let message = Message::ChessMove {
+                    player: Some(player),
+                    chess_move: parse_uci(uci).unwrap(),
+                    new_game_state,
+                    new_board,
+                };
+                let frame = message.encode().unwrap();
+                if !sent_frame_cases {
+                    sent_frame_cases = true;
+                    self.run_frame_cases(&frame)?;
+                }
+                println!("Playing {uci}");
+                self.send(*case, &frame)?;
+                if next.is_none() {
+                    self.expect_silence(*case, "peer kept playing after the game ended")?;
+                }
+                pending_case = Some(*case);
+            } else {
+                println!("Waiting for the peer to play {uci} (move {})...", index / 2 + 1);
+                let message = self.expect_message(*case, self.move_timeout)?;
+                let Some(message) = message else {
+                    self.report.fail(*case, format!("timed out waiting for the peer to play {uci}"));
+                    return Err(());
+                };
+                let mismatch = check_move(message, uci, new_game_state, &new_board);
+                if let Some(pending_case) = pending_case.take() {
+                    match &mismatch {
+                        None => self.report.pass(pending_case),
+                        Some(_) => self.report.fail(pending_case,
+                            "peer did not answer with the expected position after our move"
+                                .to_owned()),
+                    }
+                }
+                if let Some(reason) = mismatch {
+                    self.report.fail(*case, reason);
+                    return Err(());
+                }
+                self.report.pass(*case);
+            }
+            game = next;
+        }
+        if let Some(pending_case) = pending_case {
+            self.report.pass(pending_case);
+        }
+        Ok(())
+    }
+
+    /// Send each malformed variation of `valid_frame`, expecting the peer to ignore them
+    fn run_frame_cases(&mut self, valid_frame: &[u8]) -> Result<(), ()> {
+        for (case, frame) in malformed_frames(valid_frame) {
+            println!("Checking that the {}", case.description());
+            self.send(case, &frame)?;
+            self.expect_silence(case, "peer responded to an invalid frame")?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, case: Case, frame: &[u8]) -> Result<(), ()> {
+        self.transport.send_frame(frame)
+            .map_err(|e| self.report.fail(case, format!("failed to send frame: {e:?}")))
+    }
+
+    /// Wait for the quiet period, failing `case` if the peer sends anything other than a quit
+    /// message. A peer quitting is accepted, but ends the run.
+    fn expect_silence(&mut self, case: Case, reason: &str) -> Result<(), ()> {
+        match self.expect_message(case, self.quiet_period)? {
+            None => {
+                self.report.pass(case);
+                Ok(())
+            },
+            Some(Message::ChessQuit { payload }) => {
+                println!("Peer quit: {payload}");
+                self.report.pass(case);
+                Err(())
+            },
+            Some(message) => {
+                self.report.fail(case, format!("{reason}: {message:?}"));
+                Ok(())
+            },
+        }
+    }
+
+    /// Wait for the next message from the peer, answering pings along the way
+    ///
+    /// returns: The received message, or `None` if nothing was received before the timeout
+    fn expect_message(&mut self, case: Case, timeout: Duration) -> Result<Option<Message>, ()> {
+        let start = Instant::now();
+        loop {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            let frame = match wait_for_frame(self.transport.as_mut(), remaining) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    self.report.fail(case, format!("connection failed: {e:?}"));
+                    return Err(());
+                },
+            };
+            match Message::decode(&frame) {
+                Ok(Message::ChessPing { id }) => {
+                    let pong = Message::ChessPong { id }.encode().unwrap();
+                    self.send(case, &pong)?;
+                },
+                // annotations have no effect on the game, so peers may send them at any time
+                Ok(Message::ChessPong { .. } | Message::ChessAnnotate { .. }) => {},
+                Ok(message) => return Ok(Some(message)),
+                Err(()) => {
+                    self.report.fail(case, format!(
+                        "peer sent a frame that could not be decoded: {}", frame.escape_ascii()));
+                    return Err(());
+                },
+            }
+        }
+    }
+}
+
+/// Parse a move in UCI notation, such as `e2e4` or `a7a8q`
+pub fn parse_uci(uci: &str) -> Option<HalfMoveRequest> {
+    fn parse_square(square: &[u8]) -> Option<Position> {
+        let [file, rank] = square else { return None; };
+        if !(b'a'..=b'h').contains(file) || !(b'1'..=b'8').contains(rank) { return None; }
+        Position::new(file - b'a', rank - b'1')
+    }
+
+    let bytes = uci.as_bytes();
+    if bytes.len() != 4 && bytes.len() != 5 { return None; }
+    let source = parse_square(&bytes[0..2])?;
+    let dest = parse_square(&bytes[2..4])?;
+    match bytes.get(4) {
+        None => Some(HalfMoveRequest::Standard { source, dest }),
+        Some(promotion) => {
+            let kind = match promotion {
+                b'n' => PieceKind::Knight,
+                b'b' => PieceKind::Bishop,
+                b'r' => PieceKind::Rook,
+                b'q' => PieceKind::Queen,
+                _ => return None,
+            };
+            Some(HalfMoveRequest::Promotion { column: dest.column, kind })
+        },
+    }
+}
+
+/// returns: The game after the move, or `None` if it finished, along with the resulting game
+///          state and board. Returns `None` if the move is illegal.
+fn perform_scripted_move(game: Game, uci: &str) -> Option<(Option<Game>, GameStateType, Board)> {
+    match game.perform_move(parse_uci(uci)?) {
+        MoveResult::Ongoing(game, ..) => {
+            let board = game.board().clone();
+            Some((Some(game), GameStateType::Normal, board))
+        },
+        MoveResult::Finished(game) => {
+            let state = match game.result() {
+                rsoderh_chess::GameResult::Checkmate { winner: Color::White, .. } =>
+                    GameStateType::WhiteWon,
+                rsoderh_chess::GameResult::Checkmate { winner: Color::Black, .. } =>
+                    GameStateType::BlackWon,
+            };
+            Some((None, state, game.board().clone()))
+        },
+        MoveResult::Illegal(..) => None,
+    }
+}
+
+/// returns: A description of how the peer's message differs from the expected move, if it does
+fn check_move(message: Message, uci: &str, expected_state: GameStateType,
+              expected_board: &Board) -> Option<String>
+{
+    let Message::ChessMove { chess_move, new_game_state, new_board, .. } = message else {
+        return Some(format!("expected the move {uci}, got {message:?}"));
+    };
+    let expected_move = parse_uci(uci).unwrap();
+    if !moves_equal(&chess_move, &expected_move) {
+        Some(format!("expected the move {uci}, got a different move"))
+    } else if new_game_state != expected_state {
+        Some(format!("expected game state {expected_state:?}, got {new_game_state:?}"))
+    } else if new_board != *expected_board {
+        Some(format!("board after {uci} does not match"))
+    } else {
+        None
+    }
+}
+
+/// returns: The index of the first byte after the `n`th `':'` separator of the frame
+fn field_offset(frame: &[u8], n: usize) -> usize {
+    frame.iter().enumerate()
+        .filter(|(_, b)| **b == b':')
+        .nth(n - 1)
+        .map(|(i, _)| i + 1)
+        .unwrap()
+}
+
+/// returns: Variations of a valid `ChessMOVE` frame, each of which should be rejected by a
+///          conforming decoder
+pub fn malformed_frames(valid_frame: &[u8]) -> Vec<(Case, Vec<u8>)> {
+    let move_offset = field_offset(valid_frame, 1);
+    let fen_offset = field_offset(valid_frame, 3);
+
+    let mut malformed = valid_frame.to_vec();
+    malformed[move_offset..move_offset + 4].copy_from_slice(b"zz??");
+
+    let mut unknown = vec![b'0'; BUFFER_SIZE];
+    unknown[..10].copy_from_slice(b"ChessWHAT:");
+
+    let mut out_of_range = valid_frame.to_vec();
+    out_of_range[move_offset + 3] = b'9';
+
+    let mut bad_fen = valid_frame.to_vec();
+    bad_fen[fen_offset] = b'X';
+
+    let mut bad_padding = valid_frame.to_vec();
+    *bad_padding.last_mut().unwrap() = b'1';
+
+    vec![
+        (Case::MalformedFrame, malformed),
+        (Case::UnknownMessage, unknown),
+        (Case::OutOfRangeSquare, out_of_range),
+        (Case::BadFen, bad_fen),
+        (Case::BadPadding, bad_padding),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use rsoderh_chess::{Color, Game, MoveResult};
+    use crate::network::chess_tp::{GameStateType, Message, MessageReader};
+    use crate::network::transport::{MemoryTransport, Transport};
+    use super::*;
+
+    fn first_move_frame() -> Vec<u8> {
+        let (_, new_game_state, new_board) =
+            perform_scripted_move(Game::new_standard(), SCRIPT[0].0).unwrap();
+        Message::ChessMove {
+            player: Some(Color::White),
+            chess_move: parse_uci(SCRIPT[0].0).unwrap(),
+            new_game_state,
+            new_board,
+        }.encode().unwrap().to_vec()
+    }
+
+    #[test]
+    fn script_is_legal_and_ends_in_checkmate() {
+        let mut game = Game::new_standard();
+        for (index, (uci, _)) in SCRIPT.iter().enumerate() {
+            let is_last = index == SCRIPT.len() - 1;
+            match perform_scripted_move(game, uci) {
+                Some((Some(next), GameStateType::Normal, _)) if !is_last => game = next,
+                Some((None, GameStateType::WhiteWon, _)) if is_last => return,
+                _ => panic!("unexpected result after {uci}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_uci() {
+        assert!(matches!(parse_uci("e2e4"), Some(HalfMoveRequest::Standard { .. })));
+        assert!(matches!(parse_uci("a7a8q"),
+            Some(HalfMoveRequest::Promotion { kind: PieceKind::Queen, .. })));
+        assert!(parse_uci("e2e9").is_none());
+        assert!(parse_uci("i2e4").is_none());
+        assert!(parse_uci("a7a8k").is_none());
+        assert!(parse_uci("e2").is_none());
+    }
+
+    #[test]
+    fn valid_frame_is_accepted() {
+        let frame = first_move_frame();
+        assert!(Message::decode(&frame).is_ok());
+        let mut reader = MessageReader::new(&frame);
+        assert_eq!(reader.read_up_to(b':'), Ok(&b"ChessMOVE"[..]));
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        for (case, frame) in malformed_frames(&first_move_frame()) {
+            assert_eq!(frame.len(), BUFFER_SIZE, "{case:?}");
+            assert!(Message::decode(&frame).is_err(), "{case:?} was accepted");
+        }
+    }
+
+    /// Peer that follows the script as black, ignoring anything it cannot decode
+    fn reference_peer(mut transport: MemoryTransport) {
+        let mut game = Some(Game::new_standard());
+        let mut script = SCRIPT.iter().map(|(uci, _)| *uci);
+        loop {
+            let Ok(frame) = wait_for_frame(&mut transport, Duration::from_secs(5)) else { return; };
+            let Some(frame) = frame else { return; };
+            let Ok(Message::ChessMove { chess_move, .. }) = Message::decode(&frame) else {
+                continue;
+            };
+            script.next();
+            game = match game.take().unwrap().perform_move(chess_move) {
+                MoveResult::Ongoing(game, ..) => Some(game),
+                _ => return,
+            };
+            let Some(uci) = script.next() else { return; };
+            let (next, new_game_state, new_board) =
+                perform_scripted_move(game.take().unwrap(), uci).unwrap();
+            let message = Message::ChessMove {
+                player: Some(Color::Black),
+                chess_move: parse_uci(uci).unwrap(),
+                new_game_state,
+                new_board,
+            };
+            transport.send_frame(&message.encode().unwrap()).unwrap();
+            game = next;
+        }
+    }
+
+    #[test]
+    fn reference_peer_passes_all_cases() {
+        let (local, remote) = MemoryTransport::pair();
+        let peer = thread::spawn(move || reference_peer(remote));
+        let harness = Harness::new(Box::new(local), Color::White, Duration::from_secs(5),
+                                   Duration::from_millis(50));
+        let report = harness.run();
+        peer.join().unwrap();
+        assert!(report.all_passed(), "{report}");
+    }
+}
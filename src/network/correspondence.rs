@@ -0,0 +1,60 @@
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use rsoderh_chess::{Color, Game, GameResult, HalfMoveRequest, MoveResult};
+use crate::network::chess_tp::{clone_move, GameStateType, Message};
+use crate::network::transport::read_game_file_frames;
+
+const GAME_FILE_HEADER: &str =
+    "# Correspondence chess game: one chess_tp frame per line, starting with white's first move\n";
+
+/// Open the game file of a correspondence game, creating it if it does not exist yet
+///
+/// Every move in the file is replayed and checked against the board and game state recorded with
+/// it. Reading stops at the first frame that is not a move, such as a quit message. Since the file
+/// does not record which player wrote such a frame, it is not received again when the game is
+/// resumed.
+///
+/// returns: The moves made so far in order, and the number of frames in the file, which should all
+///          be treated as already received
+pub fn open_game_file(path: &Path) -> io::Result<(Vec<HalfMoveRequest>, usize)> {
+    if !path.exists() {
+        fs::write(path, GAME_FILE_HEADER)?;
+        return Ok((Vec::new(), 0));
+    }
+    let contents = fs::read(path)?;
+    let invalid = |index: usize, reason: &str| {
+        io::Error::new(ErrorKind::InvalidData, format!("move {} in game file {reason}", index + 1))
+    };
+
+    let mut moves = Vec::new();
+    let mut game = Some(Game::new_standard());
+    let frames: Vec<&[u8]> = read_game_file_frames(&contents).collect();
+    for (index, frame) in frames.iter().enumerate() {
+        let Message::ChessMove { chess_move, new_game_state, new_board, .. } =
+            Message::decode(frame).map_err(|_| invalid(index, "could not be decoded"))?
+        else { break; };
+        let current = game.take().ok_or_else(|| invalid(index, "was made after the game ended"))?;
+        let (board, game_state) = match current.perform_move(clone_move(&chess_move)) {
+            MoveResult::Ongoing(next, ..) => {
+                let board = next.board().clone();
+                game = Some(next);
+                (board, GameStateType::Normal)
+            },
+            MoveResult::Finished(finished) => {
+                let game_state = match finished.result() {
+                    GameResult::Checkmate { winner: Color::White, .. } => GameStateType::WhiteWon,
+                    GameResult::Checkmate { winner: Color::Black, .. } => GameStateType::BlackWon,
+                };
+                (finished.board().clone(), game_state)
+            },
+            MoveResult::Illegal(..) => return Err(invalid(index, "is illegal")),
+        };
+        if board != new_board || game_state != new_game_state {
+            return Err(invalid(index, "does not match the recorded board"));
+        }
+        moves.push(chess_move);
+    }
+    Ok((moves, frames.len()))
+}
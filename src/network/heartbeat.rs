@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Keeps track of the periodic ping/pong exchange used to measure latency and to detect when the
+/// other player has vanished.
+///
+/// Pings are only sent once the heartbeat has been enabled, since clients that do not support
+/// heartbeats cannot decode them.
+#[derive(Debug)]
+pub struct Heartbeat {
+    timeout: Duration,
+    enabled: bool,
+    next_ping_id: u32,
+    last_ping: Option<(u32, Instant)>,
+    last_received: Instant,
+    latency: Option<Duration>,
+}
+
+impl Heartbeat {
+    /// # Arguments
+    ///
+    /// * `timeout`: How long to wait for any frame from the other player before the connection is
+    ///   considered lost
+    pub fn new(timeout: Duration) -> Heartbeat {
+        Heartbeat {
+            timeout,
+            enabled: false,
+            next_ping_id: 0,
+            last_ping: None,
+            last_received: Instant::now(),
+            latency: None,
+        }
+    }
+
+    /// Start sending pings, because the other player is known to support heartbeats
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// returns: The id of the ping to send, if one is due
+    pub fn poll_ping(&mut self, now: Instant) -> Option<u32> {
+        if !self.enabled { return None; }
+        if self.last_ping.is_some_and(|(_, sent)| now - sent < PING_INTERVAL) {
+            return None;
+        }
+        let id = self.next_ping_id;
+        self.next_ping_id = self.next_ping_id.wrapping_add(1);
+        self.last_ping = Some((id, now));
+        Some(id)
+    }
+
+    /// Should be called whenever any frame is received from the other player
+    pub fn on_frame_received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    /// Should be called when the other player answers a ping. Answers to all but the latest ping
+    /// are ignored.
+    pub fn on_pong(&mut self, id: u32, now: Instant) {
+        if let Some((ping_id, sent)) = self.last_ping && ping_id == id {
+            self.latency = Some(now - sent);
+        }
+    }
+
+    /// returns: The round-trip time of the latest answered ping
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// returns: Whether the other player has been silent for longer than the timeout, counted from
+    ///          the start of the connection if nothing has been received yet. Without heartbeats,
+    ///          silence is expected while the other player thinks, so the connection never times
+    ///          out.
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        self.enabled && now - self.last_received > self.timeout
+    }
+
+    /// Restart the timeout, giving the other player another chance to respond
+    pub fn reset(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pings_are_only_sent_once_enabled() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(30));
+        assert_eq!(heartbeat.poll_ping(start), None);
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(60)));
+
+        heartbeat.enable();
+        assert_eq!(heartbeat.poll_ping(start), Some(0));
+        assert_eq!(heartbeat.poll_ping(start + Duration::from_secs(1)), None);
+        heartbeat.on_pong(0, start + Duration::from_millis(50));
+        assert_eq!(heartbeat.latency(), Some(Duration::from_millis(50)));
+        assert_eq!(heartbeat.poll_ping(start + PING_INTERVAL), Some(1));
+        assert!(heartbeat.is_timed_out(start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn peers_that_never_answer_time_out() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(30));
+        heartbeat.enable();
+        assert_eq!(heartbeat.poll_ping(start), Some(0));
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(10)));
+        assert!(heartbeat.is_timed_out(start + Duration::from_secs(60)));
+        heartbeat.reset(start + Duration::from_secs(60));
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(61)));
+    }
+}
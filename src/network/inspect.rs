@@ -0,0 +1,293 @@
+use std::fmt::Write;
+use clap::ValueEnum;
+use rsoderh_chess::{Board, Position, Slot};
+use crate::network::chess_tp::util::piece_to_char;
+use crate::network::chess_tp::{padding_start, Message, BUFFER_SIZE};
+use crate::network::proxy;
+use crate::network::transport::FrameMode;
+
+/// How the frames of a capture are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FrameFormat {
+    /// Binary frames stored back to back, as sent over a TCP connection
+    Raw,
+    /// One frame per line in hexadecimal, whitespace is ignored
+    Hex,
+    /// A session log, as written to the directory given by `--log-dir`
+    Log,
+    /// A timed recording, as written by the `proxy` subcommand
+    Recording,
+}
+
+/// A raw frame read from a capture, along with where it came from
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// Description of the frame's origin, such as its index or session log line
+    pub label: String,
+    pub bytes: Vec<u8>,
+}
+
+/// What was found when inspecting a frame
+#[derive(Clone, Debug)]
+pub struct Inspection {
+    pub text: String,
+    pub decoded: bool,
+    pub padding_violation: bool,
+}
+
+/// Split a capture into frames
+///
+/// # Arguments
+///
+/// * `data`: The contents of the capture
+/// * `format`: How the frames are stored in the capture
+/// * `frame_mode`: How the frames were delimited when they were sent
+///
+/// returns: The frames, or a description of why the capture could not be read
+pub fn read_frames(data: &[u8], format: FrameFormat,
+                   frame_mode: FrameMode) -> Result<Vec<Frame>, String>
+{
+    match format {
+        FrameFormat::Raw => Ok(read_raw(data, frame_mode)),
+        FrameFormat::Hex => read_hex(&String::from_utf8_lossy(data)),
+        FrameFormat::Log => read_session_log(&String::from_utf8_lossy(data)),
+        FrameFormat::Recording => read_recording(&String::from_utf8_lossy(data)),
+    }
+}
+
+/// Frames stored back to back, as they are sent over a stream. A trailing partial frame is kept,
+/// so that it is reported rather than silently dropped.
+fn read_raw(mut data: &[u8], frame_mode: FrameMode) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    while !data.is_empty() {
+        let frame = match frame_mode {
+            FrameMode::Fixed => &data[..BUFFER_SIZE.min(data.len())],
+            FrameMode::Variable => {
+                let len = match data {
+                    [high, low, ..] => u16::from_be_bytes([*high, *low]) as usize,
+                    _ => 0,
+                };
+                // skip the length header
+                data = &data[2.min(data.len())..];
+                &data[..len.min(data.len())]
+            },
+        };
+        frames.push(Frame { label: format!("#{}", frames.len() + 1), bytes: frame.to_vec() });
+        data = &data[frame.len()..];
+    }
+    frames
+}
+
+/// One frame per line in hexadecimal, ignoring whitespace, blank lines and lines starting with `#`
+fn read_hex(text: &str) -> Result<Vec<Frame>, String> {
+    let mut frames = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let digits: Vec<u8> = line.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(format!("line {}: odd number of hex digits", line_index + 1));
+        }
+        let bytes = digits.chunks(2)
+            .map(|pair| str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| format!("line {}: invalid hex digit", line_index + 1))?;
+        frames.push(Frame { label: format!("line {}", line_index + 1), bytes });
+    }
+    Ok(frames)
+}
+
+/// The frames sent and received in a session log, ignoring all other entries
+fn read_session_log(text: &str) -> Result<Vec<Frame>, String> {
+    let mut frames = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let Some((timestamp, entry)) = line.strip_prefix('[').and_then(|line| line.split_once("] "))
+            else { continue; };
+        let Some((kind, escaped)) = entry.split_once(' ') else { continue; };
+        if kind != "SEND" && kind != "RECV" { continue; }
+        let bytes = unescape_ascii(escaped)
+            .ok_or_else(|| format!("line {}: invalid escape sequence", line_index + 1))?;
+        frames.push(Frame { label: format!("{kind} at {timestamp}"), bytes });
+    }
+    Ok(frames)
+}
+
+/// The frames of a proxy recording, labeled with their direction and when they were forwarded
+fn read_recording(text: &str) -> Result<Vec<Frame>, String> {
+    let frames = proxy::parse_recording(text).map_err(|e| e.to_string())?;
+    Ok(frames.into_iter()
+        .map(|frame| Frame {
+            label: format!("{} at {} ms", proxy::direction_name(frame.sender),
+                           frame.elapsed.as_millis()),
+            bytes: frame.bytes,
+        })
+        .collect())
+}
+
+/// Reverse of [`<[u8]>::escape_ascii`], which is used to write frames to session logs
+pub fn unescape_ascii(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = text.bytes();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let escaped = match chars.next()? {
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'n' => b'\n',
+            b'\\' => b'\\',
+            b'\'' => b'\'',
+            b'"' => b'"',
+            b'x' => {
+                let digits = [chars.next()?, chars.next()?];
+                u8::from_str_radix(str::from_utf8(&digits).ok()?, 16).ok()?
+            },
+            _ => return None,
+        };
+        bytes.push(escaped);
+    }
+    Some(bytes)
+}
+
+/// Decode a frame and describe it in a human-readable form, including the board of move messages
+/// and any bytes that violate the padding rules
+///
+/// # Arguments
+///
+/// * `frame_mode`: How the frame was delimited, variable-length frames may have any length
+pub fn inspect(frame: &Frame, frame_mode: FrameMode) -> Inspection {
+    let mut text = String::new();
+    let bytes = &frame.bytes;
+    let _ = writeln!(text, "Frame {} ({} bytes)", frame.label, bytes.len());
+    let _ = writeln!(text, "  raw:     {}", bytes.escape_ascii());
+
+    let decoded = Message::decode(bytes);
+    match &decoded {
+        Ok(message) => { let _ = writeln!(text, "  decoded: {message:?}"); },
+        Err(()) => { let _ = writeln!(text, "  decoded: FAILED"); },
+    }
+
+    let mut padding_violation = frame_mode == FrameMode::Fixed && bytes.len() != BUFFER_SIZE;
+    if padding_violation {
+        let _ = writeln!(text, "  length:  VIOLATION, expected {BUFFER_SIZE} bytes");
+    }
+    match padding_start(bytes) {
+        None => { let _ = writeln!(text, "  padding: unknown, message arguments could not be located"); },
+        Some(start) => {
+            let violations: Vec<String> = bytes[start..].iter().enumerate()
+                .filter(|(_, byte)| **byte != b'0')
+                .map(|(index, byte)| format!("{} ({})", start + index, [*byte].escape_ascii()))
+                .collect();
+            if violations.is_empty() {
+                let _ = writeln!(text, "  padding: ok, {} bytes from offset {start}", bytes.len() - start);
+            } else {
+                padding_violation = true;
+                let _ = writeln!(text, "  padding: VIOLATION, non-'0' bytes at offsets {}",
+                                 violations.join(", "));
+            }
+        },
+    }
+
+    if let Ok(Message::ChessMove { new_board, .. }) = &decoded {
+        let _ = writeln!(text, "  board:");
+        text.push_str(&board_to_ascii(new_board, "    "));
+    }
+
+    Inspection { text, decoded: decoded.is_ok(), padding_violation }
+}
+
+/// Render a board from white's point of view, with uppercase letters for white pieces, lowercase
+/// letters for black pieces and `.` for empty squares
+fn board_to_ascii(board: &Board, indent: &str) -> String {
+    let mut text = String::new();
+    for row in (0..8).rev() {
+        let _ = write!(text, "{indent}{} ", row + 1);
+        for column in 0..8 {
+            let square = match board.at_position(Position::new(column, row).unwrap()) {
+                Slot::Empty => '.',
+                Slot::Occupied(piece) => piece_to_char(piece) as char,
+            };
+            let _ = write!(text, " {square}");
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(text, "{indent}   a b c d e f g h");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quit_frame() -> Vec<u8> {
+        Message::ChessQuit { payload: "bye".to_owned() }.encode().unwrap().to_vec()
+    }
+
+    #[test]
+    fn unescapes_session_log_frames() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let escaped = bytes.escape_ascii().to_string();
+        assert_eq!(unescape_ascii(&escaped), Some(bytes));
+        assert_eq!(unescape_ascii("\\q"), None);
+        assert_eq!(unescape_ascii("\\x4"), None);
+    }
+
+    #[test]
+    fn reads_session_log() {
+        let log = format!(
+            "[2025-01-01T00:00:00.000Z] EVENT Connected\n[2025-01-01T00:00:01.000Z] RECV {}\n",
+            quit_frame().escape_ascii());
+        let frames = read_frames(log.as_bytes(), FrameFormat::Log, FrameMode::Fixed).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].bytes, quit_frame());
+    }
+
+    #[test]
+    fn reads_proxy_recording() {
+        let recording = format!(
+            "# chess_tp recording\n1500 join->host {}\n# Failed to forward join->host\n",
+            quit_frame().escape_ascii());
+        let frames = read_frames(recording.as_bytes(), FrameFormat::Recording, FrameMode::Fixed)
+            .unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].label, "join->host at 1500 ms");
+        assert_eq!(frames[0].bytes, quit_frame());
+        let malformed = b"soon host->join x";
+        assert!(read_frames(malformed, FrameFormat::Recording, FrameMode::Fixed).is_err());
+    }
+
+    #[test]
+    fn reads_hex() {
+        let frames = read_frames(b"# capture\n43 68\n\n6573\n", FrameFormat::Hex, FrameMode::Fixed).unwrap();
+        assert_eq!(frames.iter().map(|frame| frame.bytes.clone()).collect::<Vec<_>>(),
+                   vec![b"Ch".to_vec(), b"es".to_vec()]);
+        assert!(read_frames(b"436", FrameFormat::Hex, FrameMode::Fixed).is_err());
+    }
+
+    #[test]
+    fn flags_padding_violations() {
+        let frame = Frame { label: String::new(), bytes: quit_frame() };
+        let inspection = inspect(&frame, FrameMode::Fixed);
+        assert!(inspection.decoded && !inspection.padding_violation);
+
+        let mut bytes = quit_frame();
+        bytes[100] = b'x';
+        let inspection = inspect(&Frame { label: String::new(), bytes }, FrameMode::Fixed);
+        assert!(!inspection.decoded && inspection.padding_violation);
+        assert!(inspection.text.contains("offsets 100 (x)"), "{}", inspection.text);
+
+        let short_frame = Frame { label: String::new(), bytes: quit_frame()[..64].to_vec() };
+        assert!(inspect(&short_frame, FrameMode::Fixed).padding_violation);
+        assert!(!inspect(&short_frame, FrameMode::Variable).padding_violation);
+    }
+
+    #[test]
+    fn reads_length_prefixed_frames() {
+        let data = [&[0, 2][..], b"ab", &[0, 3], b"cd"].concat();
+        let frames = read_frames(&data, FrameFormat::Raw, FrameMode::Variable).unwrap();
+        assert_eq!(frames.iter().map(|frame| frame.bytes.clone()).collect::<Vec<_>>(),
+                   vec![b"ab".to_vec(), b"cd".to_vec()]);
+    }
+}
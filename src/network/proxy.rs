@@ -0,0 +1,294 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::network::chess_tp::Message;
+use crate::network::inspect::unescape_ascii;
+use crate::network::transport::{wait_for_frame, Role, Transport, TransportError, POLL_INTERVAL};
+
+/// A frame that passed through the proxy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedFrame {
+    /// Time since the proxy started forwarding frames
+    pub elapsed: Duration,
+    /// The side of the connection that sent the frame
+    pub sender: Role,
+    pub bytes: Vec<u8>,
+}
+
+impl RecordedFrame {
+    fn to_line(&self) -> String {
+        format!("{} {} {}", self.elapsed.as_millis(), direction_name(self.sender), self.bytes.escape_ascii())
+    }
+
+    fn from_line(line: &str) -> Option<RecordedFrame> {
+        let mut parts = line.splitn(3, ' ');
+        let elapsed = Duration::from_millis(parts.next()?.parse().ok()?);
+        let sender = match parts.next()? {
+            "host->join" => Role::Host,
+            "join->host" => Role::Join,
+            _ => return None,
+        };
+        let bytes = unescape_ascii(parts.next()?)?;
+        Some(RecordedFrame { elapsed, sender, bytes })
+    }
+}
+
+pub fn direction_name(sender: Role) -> &'static str {
+    match sender {
+        Role::Host => "host->join",
+        Role::Join => "join->host",
+    }
+}
+
+/// Read a recording written by [run_proxy]
+///
+/// returns: The recorded frames in the order they were forwarded
+pub fn read_recording(path: &Path) -> io::Result<Vec<RecordedFrame>> {
+    parse_recording(&fs::read_to_string(path)?)
+}
+
+/// Parse the contents of a recording written by [run_proxy], skipping blank lines and comments
+///
+/// returns: The recorded frames in the order they were forwarded
+pub fn parse_recording(text: &str) -> io::Result<Vec<RecordedFrame>> {
+    let mut frames = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let frame = RecordedFrame::from_line(line).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData, format!("line {}: malformed recorded frame", line_index + 1)))?;
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+/// Forward frames between a host and a joiner until either side disconnects, recording every frame
+/// along with when it was forwarded
+///
+/// # Arguments
+///
+/// * `host`: Transport connected to the hosting player
+/// * `join`: Transport connected to the joining player
+/// * `recording`: Where to write the recording, one frame per line
+pub fn run_proxy(mut host: Box<dyn Transport>, mut join: Box<dyn Transport>,
+                 recording: &mut dyn Write) -> io::Result<()>
+{
+    writeln!(recording, "# chess_tp recording: <milliseconds> <direction> <frame>")?;
+    let start = Instant::now();
+    let result = loop {
+        let host_to_join = forward(Role::Host, host.as_mut(), join.as_mut(), start, recording);
+        let join_to_host = forward(Role::Join, join.as_mut(), host.as_mut(), start, recording);
+        let forwarded = match (host_to_join, join_to_host) {
+            (Ok(host_to_join), Ok(join_to_host)) => (host_to_join, join_to_host),
+            (Err(e), _) | (_, Err(e)) => break Err(e),
+        };
+        match forwarded {
+            (Forwarded::Disconnected, _) | (_, Forwarded::Disconnected) => {
+                println!("Connection closed");
+                break Ok(());
+            },
+            (Forwarded::Nothing, Forwarded::Nothing) => thread::sleep(POLL_INTERVAL),
+            _ => {},
+        }
+    };
+    let _ = host.close();
+    let _ = join.close();
+    result
+}
+
+enum Forwarded {
+    Frame,
+    Nothing,
+    Disconnected,
+}
+
+/// Forward a single frame from `from` to `to` if one has been received, recording it
+///
+/// returns: An error if either transport failed for any other reason than being closed, which is
+///          also written to the recording
+fn forward(sender: Role, from: &mut dyn Transport, to: &mut dyn Transport, start: Instant,
+           recording: &mut dyn Write) -> io::Result<Forwarded>
+{
+    let direction = direction_name(sender);
+    match from.poll_frame() {
+        Ok(Some(bytes)) => {
+            let frame = RecordedFrame { elapsed: start.elapsed(), sender, bytes };
+            println!("{}", frame.to_line());
+            writeln!(recording, "{}", frame.to_line())?;
+            // flush every frame, so that the recording is complete even if the proxy is killed
+            recording.flush()?;
+            match to.send_frame(&frame.bytes) {
+                Ok(()) => Ok(Forwarded::Frame),
+                Err(TransportError::Disconnected) => Ok(Forwarded::Disconnected),
+                Err(e) => {
+                    Err(record_error(recording, &format!("Failed to forward {direction}: {e:?}"))?)
+                },
+            }
+        },
+        Ok(None) => Ok(Forwarded::Nothing),
+        Err(TransportError::Unauthenticated) => {
+            record_error(recording, &format!("Dropped unauthenticated frame {direction}"))?;
+            Ok(Forwarded::Nothing)
+        },
+        Err(TransportError::Disconnected) => Ok(Forwarded::Disconnected),
+        Err(e) => Err(record_error(recording, &format!("Failed to receive {direction}: {e:?}"))?),
+    }
+}
+
+/// Print a problem with one of the transports and write it to the recording as a comment, which
+/// [read_recording] skips
+///
+/// returns: An error describing the problem
+fn record_error(recording: &mut dyn Write, text: &str) -> io::Result<io::Error> {
+    println!("{text}");
+    writeln!(recording, "# {text}")?;
+    recording.flush()?;
+    Ok(io::Error::other(text.to_owned()))
+}
+
+/// Play back one side of a recording against a live client, acting as its opponent
+///
+/// Frames sent by the replayed side are sent with the same delays as in the recording. Frames that
+/// were sent by the live side are awaited and compared against the recording, and any difference is
+/// reported. Heartbeats are not replayed, but pings from the live client are answered.
+///
+/// # Arguments
+///
+/// * `transport`: Transport connected to the live client
+/// * `frames`: The recording to play back
+/// * `replayed_side`: Which side of the recorded connection to act as
+/// * `timeout`: How long to wait for each frame from the live client
+///
+/// returns: The number of frames from the live client that differed from the recording, or were
+///          never received
+pub fn replay(transport: &mut dyn Transport, frames: &[RecordedFrame], replayed_side: Role,
+              timeout: Duration) -> Result<usize, TransportError>
+{
+    let is_heartbeat = |bytes: &[u8]| {
+        matches!(Message::decode(bytes), Ok(Message::ChessPing { .. } | Message::ChessPong { .. }))
+    };
+    let mut mismatches = 0;
+    let mut previous_elapsed = Duration::ZERO;
+    for (index, frame) in frames.iter().enumerate() {
+        if is_heartbeat(&frame.bytes) { continue; }
+        let delay = frame.elapsed.saturating_sub(previous_elapsed);
+        previous_elapsed = frame.elapsed;
+
+        if frame.sender == replayed_side {
+            thread::sleep(delay);
+            println!("#{} SEND {}", index + 1, frame.bytes.escape_ascii());
+            transport.send_frame(&frame.bytes)?;
+            continue;
+        }
+
+        let expected_from = direction_name(replayed_side.other());
+        let received = loop {
+            let Some(received) = wait_for_frame(transport, timeout)? else { break None; };
+            match Message::decode(&received) {
+                Ok(Message::ChessPing { id }) => {
+                    transport.send_frame(&Message::ChessPong { id }.encode().unwrap())?;
+                },
+                Ok(Message::ChessPong { .. }) => {},
+                _ => break Some(received),
+            }
+        };
+        match received {
+            Some(received) if received == frame.bytes => {
+                println!("#{} RECV {} (matches recording)", index + 1, received.escape_ascii());
+            },
+            Some(received) => {
+                mismatches += 1;
+                println!("#{} RECV {} (MISMATCH)", index + 1, received.escape_ascii());
+                println!("#{} recorded {expected_from}: {}", index + 1, frame.bytes.escape_ascii());
+            },
+            None => {
+                mismatches += 1;
+                println!("#{} timed out waiting for {expected_from}: {}", index + 1,
+                         frame.bytes.escape_ascii());
+            },
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    #[cfg(unix)]
+    use std::os::unix::net::UnixStream;
+    #[cfg(unix)]
+    use crate::network::transport::StreamTransport;
+    use crate::network::transport::MemoryTransport;
+    use super::*;
+
+    fn quit_frame(payload: &str) -> Vec<u8> {
+        Message::ChessQuit { payload: payload.to_owned() }.encode().unwrap().to_vec()
+    }
+
+    #[test]
+    fn recorded_frame_round_trips() {
+        let frame = RecordedFrame {
+            elapsed: Duration::from_millis(1234),
+            sender: Role::Join,
+            bytes: (0..=255).collect(),
+        };
+        assert_eq!(RecordedFrame::from_line(&frame.to_line()), Some(frame));
+    }
+
+    #[test]
+    fn proxy_forwards_and_records() {
+        let (host_proxy, mut host) = MemoryTransport::pair();
+        let (join_proxy, mut join) = MemoryTransport::pair();
+        let proxy = thread::spawn(move || {
+            let mut recording = Vec::new();
+            run_proxy(Box::new(host_proxy), Box::new(join_proxy), &mut recording).unwrap();
+            recording
+        });
+
+        join.send_frame(&quit_frame("from join")).unwrap();
+        let forwarded = wait_for_frame(&mut host, Duration::from_secs(5)).unwrap();
+        assert_eq!(forwarded, Some(quit_frame("from join")));
+        host.close().unwrap();
+
+        let recording = String::from_utf8(proxy.join().unwrap()).unwrap();
+        let frames: Vec<_> = recording.lines().skip(1).map(RecordedFrame::from_line).collect();
+        assert_eq!(frames.len(), 1);
+        let frame = frames[0].as_ref().unwrap();
+        assert_eq!((frame.sender, &frame.bytes), (Role::Join, &quit_frame("from join")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn proxy_stops_and_records_transport_errors() {
+        let (host_proxy, mut host) = MemoryTransport::pair();
+        let (join_proxy, _join) = UnixStream::pair().unwrap();
+        join_proxy.set_nonblocking(true).unwrap();
+        // the joining leg only accepts frames of the fixed size
+        host.send_frame(b"short").unwrap();
+        let mut recording = Vec::new();
+        let result = run_proxy(Box::new(host_proxy), Box::new(StreamTransport::new(join_proxy)),
+                               &mut recording);
+
+        assert!(result.is_err());
+        let recording = String::from_utf8(recording).unwrap();
+        let expected = "# Failed to forward host->join: InvalidFrame";
+        assert!(recording.lines().any(|line| line == expected), "{recording}");
+    }
+
+    #[test]
+    fn replay_plays_back_and_compares() {
+        let frames = vec![
+            RecordedFrame { elapsed: Duration::ZERO, sender: Role::Join, bytes: quit_frame("a") },
+            RecordedFrame { elapsed: Duration::from_millis(10), sender: Role::Host, bytes: quit_frame("b") },
+            RecordedFrame { elapsed: Duration::from_millis(20), sender: Role::Join, bytes: quit_frame("c") },
+        ];
+        let (mut replayer, mut client) = MemoryTransport::pair();
+        client.send_frame(&quit_frame("a")).unwrap();
+        client.send_frame(&quit_frame("not c")).unwrap();
+        let mismatches = replay(&mut replayer, &frames, Role::Host, Duration::from_secs(5)).unwrap();
+        assert_eq!(mismatches, 1);
+        assert_eq!(wait_for_frame(&mut client, Duration::from_secs(5)).unwrap(), Some(quit_frame("b")));
+    }
+}
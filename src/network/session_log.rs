@@ -0,0 +1,113 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rsoderh_chess::Color;
+use crate::network::chess_tp::Message;
+
+/// Log of everything that happens on a single connection, written to its own file so that remote
+/// games can be debugged after the fact
+#[derive(Debug)]
+pub struct SessionLog {
+    file: File,
+}
+
+impl SessionLog {
+    /// Create a new log file in `directory`, creating the directory if it does not exist
+    pub fn create(directory: &Path, local_player: Color) -> io::Result<SessionLog> {
+        fs::create_dir_all(directory)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let role = match local_player {
+            Color::White => "white",
+            Color::Black => "black",
+        };
+        let path = directory.join(format!("session-{}-{role}.log", now.as_millis()));
+        Ok(SessionLog { file: File::create(path)? })
+    }
+
+    pub fn frame_sent(&mut self, frame: &[u8]) {
+        self.write_line(&format!("SEND {}", escape_frame(frame)));
+    }
+
+    pub fn frame_received(&mut self, frame: &[u8]) {
+        self.write_line(&format!("RECV {}", escape_frame(frame)));
+    }
+
+    pub fn decoded(&mut self, result: &Result<Message, ()>) {
+        match result {
+            Ok(message) => self.write_line(&format!("DECODE {message:?}")),
+            Err(()) => self.write_line("DECODE failed"),
+        }
+    }
+
+    /// Log anything else that happened during the session, such as desyncs and quit reasons
+    pub fn event(&mut self, text: &str) {
+        self.write_line(&format!("EVENT {text}"));
+    }
+
+    fn write_line(&mut self, line: &str) {
+        // logging is best-effort, and should never interrupt the game
+        let _ = writeln!(self.file, "[{}] {line}", format_timestamp(SystemTime::now()));
+    }
+}
+
+/// returns: The frame as ASCII text, with any other bytes escaped
+fn escape_frame(frame: &[u8]) -> String {
+    frame.escape_ascii().to_string()
+}
+
+/// returns: The time as an ISO 8601 UTC timestamp with millisecond precision
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_of_day = seconds % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// Convert a number of days since 1970-01-01 to a (year, month, day) date in the proleptic
+/// Gregorian calendar
+///
+/// see: <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(23206), (2033, 7, 15));
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap_day = UNIX_EPOCH + Duration::from_millis(1_709_251_199_999);
+        assert_eq!(format_timestamp(leap_day), "2024-02-29T23:59:59.999Z");
+        let later = UNIX_EPOCH + Duration::from_millis(2_005_047_255_123);
+        assert_eq!(format_timestamp(later), "2033-07-15T13:34:15.123Z");
+    }
+}
@@ -0,0 +1,317 @@
+mod encrypted;
+mod file;
+#[cfg(test)]
+mod memory;
+mod negotiation;
+mod stream;
+#[cfg(feature = "websocket")]
+mod websocket;
+
+use std::fmt::Debug;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use clap::ValueEnum;
+use crate::network::chess_tp;
+
+pub use encrypted::EncryptedTransport;
+pub use file::{read_game_file_frames, FileTransport};
+#[cfg(test)]
+pub use memory::MemoryTransport;
+pub use stream::StreamTransport;
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketTransport;
+
+#[cfg(feature = "websocket")]
+const WEBSOCKET_SCHEME: &str = "ws://";
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransportError {
+    /// The connection was closed or failed
+    Disconnected,
+    /// The frame does not have a size that can be sent over this transport
+    InvalidFrame,
+    /// A received frame failed authentication, and may have been tampered with
+    Unauthenticated,
+}
+
+/// The kind of connection that carries the frames of a remote game
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TransportKind {
+    /// TCP connection, `ADDRESS` is an IPv4/IPv6 address and port
+    Tcp,
+    /// Unix domain socket, `ADDRESS` is the path of the socket file
+    Unix,
+    /// WebSocket connection with binary messages, `ADDRESS` is an IPv4/IPv6 address and port, or a
+    /// `ws://` URL when joining, requires the `websocket` feature
+    #[value(name = "websocket")]
+    WebSocket,
+}
+
+/// How frames are delimited on a transport
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FrameMode {
+    /// Every message is padded to 128 bytes, compatible with all peers
+    Fixed,
+    /// Messages are sent with a length header instead of padding, and may be longer than 128 bytes
+    Variable,
+}
+
+/// Which side of the connection setup the local player is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Host,
+    Join,
+}
+
+impl Role {
+    /// returns: The role of the other end of the connection
+    pub fn other(self) -> Role {
+        match self {
+            Role::Host => Role::Join,
+            Role::Join => Role::Host,
+        }
+    }
+}
+
+/// A bidirectional, frame-oriented channel between the two players of a remote game.
+///
+/// Implementations are expected to be non-blocking, so that they can be polled from the GUI event
+/// loop every frame.
+pub trait Transport: Debug {
+    /// Send a single frame to the other end of the transport
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError>;
+
+    /// returns: The next received frame, or `None` if no complete frame is available yet
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError>;
+
+    /// Close the transport, after which no more frames can be sent or received
+    fn close(&mut self) -> Result<(), TransportError>;
+
+    /// returns: How frames are delimited, and thereby how messages should be encoded
+    fn frame_mode(&self) -> FrameMode;
+
+    /// Switch how frames are delimited, affecting all frames sent and received afterwards
+    fn set_frame_mode(&mut self, frame_mode: FrameMode);
+}
+
+/// Block until a frame is received, polling the transport at a fixed interval
+pub fn wait_for_frame(transport: &mut dyn Transport,
+                      timeout: Duration) -> Result<Option<Vec<u8>>, TransportError>
+{
+    let start = Instant::now();
+    loop {
+        if let Some(frame) = transport.poll_frame()? {
+            return Ok(Some(frame));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// returns: The size of the frames sent over stream transports
+fn stream_frame_size(passphrase: Option<&str>) -> usize {
+    if passphrase.is_some() { encrypted::SEALED_FRAME_SIZE } else { chess_tp::BUFFER_SIZE }
+}
+
+/// If a passphrase is given, authenticate the other player and encrypt all further frames
+fn secure(transport: Box<dyn Transport>, passphrase: Option<&str>,
+          role: Role) -> io::Result<Box<dyn Transport>>
+{
+    match passphrase {
+        Some(passphrase) => Ok(Box::new(EncryptedTransport::handshake(transport, passphrase, role)?)),
+        None => Ok(transport),
+    }
+}
+
+fn negotiation_failed(_: TransportError) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "connection lost while negotiating frame mode")
+}
+
+/// Bind a server to `address` and wait for a single player to connect. Connections that fail to
+/// set up or authenticate are logged and dropped, and the server keeps waiting for another player.
+///
+/// # Arguments
+///
+/// * `frame_mode`: The frame mode to allow, if the other player requests it
+///
+/// returns: The established transport, along with a description of the connected peer
+pub fn host(kind: TransportKind, address: &str, passphrase: Option<&str>,
+            frame_mode: FrameMode) -> io::Result<(Box<dyn Transport>, String)>
+{
+    let frame_size = stream_frame_size(passphrase);
+    let (transport, other_address) = match kind {
+        TransportKind::Tcp => {
+            let listener = TcpListener::bind(address)?;
+            accept_authenticated(passphrase, || {
+                let (stream, other_address) = listener.accept()?;
+                let transport = stream.set_nonblocking(true).map(|_| -> Box<dyn Transport> {
+                    Box::new(StreamTransport::with_frame_size(stream, frame_size))
+                });
+                Ok((transport, other_address.to_string()))
+            })?
+        },
+        #[cfg(unix)]
+        TransportKind::Unix => {
+            let listener = UnixListener::bind(address)?;
+            accept_authenticated(passphrase, || {
+                let (stream, _) = listener.accept()?;
+                let transport = stream.set_nonblocking(true).map(|_| -> Box<dyn Transport> {
+                    Box::new(StreamTransport::with_frame_size(stream, frame_size))
+                });
+                Ok((transport, address.to_owned()))
+            })?
+        },
+        #[cfg(not(unix))]
+        TransportKind::Unix => return Err(unix_unsupported()),
+        #[cfg(feature = "websocket")]
+        TransportKind::WebSocket => {
+            let listener = TcpListener::bind(address)?;
+            accept_authenticated(passphrase, || {
+                let (stream, other_address) = listener.accept()?;
+                let transport = WebSocketTransport::accept(stream).and_then(|transport| {
+                    transport.get_ref().set_nonblocking(true)?;
+                    Ok(Box::new(transport) as Box<dyn Transport>)
+                });
+                Ok((transport, other_address.to_string()))
+            })?
+        },
+        #[cfg(not(feature = "websocket"))]
+        TransportKind::WebSocket => return Err(websocket_unsupported()),
+    };
+    // frame modes are negotiated inside the encrypted channel, so that they cannot be mistaken
+    // for handshake frames
+    let transport = negotiation::negotiate_as_host(transport, frame_mode, chess_tp::BUFFER_SIZE);
+    Ok((transport, other_address))
+}
+
+/// A connection accepted by a server, with the result of setting up its transport and a description
+/// of the peer
+type Accepted = (io::Result<Box<dyn Transport>>, String);
+
+/// Accept connections until one of them is set up and authenticated
+///
+/// # Arguments
+///
+/// * `accept`: Blocks until the next connection is accepted. Errors of the listener itself are
+///   returned, and stop the server.
+///
+/// returns: The first authenticated transport, along with a description of its peer
+fn accept_authenticated(passphrase: Option<&str>, mut accept: impl FnMut() -> io::Result<Accepted>)
+                        -> io::Result<(Box<dyn Transport>, String)>
+{
+    loop {
+        let (transport, other_address) = accept()?;
+        match transport.and_then(|transport| secure(transport, passphrase, Role::Host)) {
+            Ok(transport) => return Ok((transport, other_address)),
+            Err(e) => eprintln!("Dropped the connection from {other_address}: {e}"),
+        }
+    }
+}
+
+/// Connect to a game server at `address`
+///
+/// # Arguments
+///
+/// * `frame_mode`: The frame mode to request from the host
+pub fn join(kind: TransportKind, address: &str, passphrase: Option<&str>,
+            frame_mode: FrameMode) -> io::Result<Box<dyn Transport>>
+{
+    let frame_size = stream_frame_size(passphrase);
+    let transport: Box<dyn Transport> = match kind {
+        TransportKind::Tcp => {
+            let stream = TcpStream::connect(address)?;
+            stream.set_nonblocking(true)?;
+            Box::new(StreamTransport::with_frame_size(stream, frame_size))
+        },
+        #[cfg(unix)]
+        TransportKind::Unix => {
+            let stream = UnixStream::connect(address)?;
+            stream.set_nonblocking(true)?;
+            Box::new(StreamTransport::with_frame_size(stream, frame_size))
+        },
+        #[cfg(not(unix))]
+        TransportKind::Unix => return Err(unix_unsupported()),
+        #[cfg(feature = "websocket")]
+        TransportKind::WebSocket => {
+            let (url, socket_address) = websocket_url(address);
+            let stream = TcpStream::connect(socket_address)?;
+            let transport = WebSocketTransport::connect(&url, stream)?;
+            transport.get_ref().set_nonblocking(true)?;
+            Box::new(transport)
+        },
+        #[cfg(not(feature = "websocket"))]
+        TransportKind::WebSocket => return Err(websocket_unsupported()),
+    };
+    let transport = secure(transport, passphrase, Role::Join)?;
+    negotiation::negotiate_as_join(transport, frame_mode, chess_tp::BUFFER_SIZE)
+        .map_err(negotiation_failed)
+}
+
+/// Accepts either a full `ws://` URL or a plain socket address, which is then requested at the root
+/// path.
+///
+/// returns: The URL to request, along with the socket address of the server
+#[cfg(feature = "websocket")]
+fn websocket_url(address: &str) -> (String, &str) {
+    match address.strip_prefix(WEBSOCKET_SCHEME) {
+        Some(rest) => {
+            let socket_address = rest.split('/').next().unwrap_or(rest);
+            (address.to_owned(), socket_address)
+        },
+        None => (format!("{WEBSOCKET_SCHEME}{address}/"), address),
+    }
+}
+
+#[cfg(not(feature = "websocket"))]
+fn websocket_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "WebSocket support was not enabled in this build")
+}
+
+#[cfg(not(unix))]
+fn unix_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "Unix domain sockets are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// returns: A transport joined to the host at `path`, once the host has bound it
+    #[cfg(unix)]
+    fn join_when_bound(path: &str, passphrase: &str) -> io::Result<Box<dyn Transport>> {
+        while !std::path::Path::new(path).exists() {
+            thread::sleep(POLL_INTERVAL);
+        }
+        join(TransportKind::Unix, path, Some(passphrase), FrameMode::Fixed)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn host_keeps_accepting_after_a_failed_handshake() {
+        let path = std::env::temp_dir().join(format!("chess-host-{}", std::process::id()));
+        let path = path.to_str().unwrap().to_owned();
+        let _ = std::fs::remove_file(&path);
+        let join = {
+            let path = path.clone();
+            thread::spawn(move || {
+                assert!(join_when_bound(&path, "guess").is_err());
+                let mut join = join_when_bound(&path, "secret").unwrap();
+                join.send_frame(&[1; chess_tp::BUFFER_SIZE]).unwrap();
+            })
+        };
+
+        let (mut host, _) = host(TransportKind::Unix, &path, Some("secret"), FrameMode::Fixed).unwrap();
+        let frame = wait_for_frame(host.as_mut(), Duration::from_secs(5)).unwrap();
+        assert_eq!(frame, Some(vec![1; chess_tp::BUFFER_SIZE]));
+        join.join().unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::io::ErrorKind;
+use std::time::Duration;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use chacha20poly1305::aead::Aead;
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::traits::IsIdentity;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use crate::network::chess_tp;
+use crate::network::transport::{wait_for_frame, FrameMode, Role, Transport, TransportError};
+
+const TAG_SIZE: usize = 16;
+/// The size of a sealed `chess_tp` message, including its authentication tag
+pub const SEALED_FRAME_SIZE: usize = chess_tp::BUFFER_SIZE + TAG_SIZE;
+
+const SHARE_IDENTIFIER: &[u8] = b"ChessPAKE:";
+const PROOF_IDENTIFIER: &[u8] = b"ChessPROOF:";
+const VALUE_SIZE: usize = 32;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const GENERATOR_PURPOSE: &[u8] = b"generator:";
+const MASTER_KEY_PURPOSE: &[u8] = b"master key:";
+const PROOF_PURPOSE: &[u8] = b"proof:";
+const KEY_PURPOSE: &[u8] = b"key:";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakeError {
+    Transport(TransportError),
+    /// The other player did not respond in time
+    Timeout,
+    /// The other player sent something other than the expected handshake frame
+    Malformed,
+    /// No random secret could be generated
+    Random,
+    /// The other player could not prove knowledge of the passphrase
+    AuthenticationFailed,
+}
+
+impl From<TransportError> for HandshakeError {
+    fn from(error: TransportError) -> Self {
+        HandshakeError::Transport(error)
+    }
+}
+
+impl From<HandshakeError> for io::Error {
+    fn from(error: HandshakeError) -> Self {
+        let (kind, message) = match error {
+            HandshakeError::Transport(_) =>
+                (ErrorKind::ConnectionAborted, "connection lost during handshake"),
+            HandshakeError::Timeout =>
+                (ErrorKind::TimedOut, "other player did not complete the handshake"),
+            HandshakeError::Malformed =>
+                (ErrorKind::InvalidData, "other player sent an invalid handshake frame"),
+            HandshakeError::Random =>
+                (ErrorKind::Other, "failed to generate a random secret"),
+            HandshakeError::AuthenticationFailed =>
+                (ErrorKind::PermissionDenied, "other player used a different passphrase"),
+        };
+        io::Error::new(kind, message)
+    }
+}
+
+/// [Transport] wrapper that authenticates and encrypts every frame with a key derived from a
+/// passphrase shared by both players.
+///
+/// The handshake is a CPace-style password-authenticated key exchange over Ristretto255: both
+/// players derive a generator from the passphrase, exchange a random multiple of it, and derive a
+/// master key from the shared point and both shares. Since a share reveals nothing about the
+/// passphrase, and a proof can only be checked against the one passphrase its receiver committed to
+/// in its own share, an attacker learns at most whether a single guess was right per connection.
+/// The players then prove knowledge of the master key to each other, the host first. Each direction
+/// then uses its own ChaCha20-Poly1305 key, with a frame counter as the nonce.
+pub struct EncryptedTransport {
+    inner: Box<dyn Transport>,
+    send_cipher: ChaCha20Poly1305,
+    receive_cipher: ChaCha20Poly1305,
+    send_counter: u64,
+    receive_counter: u64,
+}
+
+impl EncryptedTransport {
+    /// Perform the handshake over `inner`, blocking until it completes
+    pub fn handshake(mut inner: Box<dyn Transport>, passphrase: &str,
+                     role: Role) -> Result<EncryptedTransport, HandshakeError>
+    {
+        let mut secret = [0u8; 2 * VALUE_SIZE];
+        getrandom::getrandom(&mut secret).map_err(|_| HandshakeError::Random)?;
+        let secret = Scalar::from_bytes_mod_order_wide(&secret);
+        let local_share = (generator(passphrase) * secret).compress().to_bytes();
+        inner.send_frame(&handshake_frame(SHARE_IDENTIFIER, &local_share))?;
+        let remote_share = receive_handshake_value(inner.as_mut(), SHARE_IDENTIFIER)?;
+        let remote_point = CompressedRistretto(remote_share).decompress()
+            .filter(|point| !point.is_identity())
+            .ok_or(HandshakeError::Malformed)?;
+
+        let transcript = match role {
+            Role::Host => [local_share, remote_share].concat(),
+            Role::Join => [remote_share, local_share].concat(),
+        };
+        let shared_point = (remote_point * secret).compress();
+        let master_key: [u8; VALUE_SIZE] = Sha256::new()
+            .chain_update(MASTER_KEY_PURPOSE)
+            .chain_update(shared_point.as_bytes())
+            .chain_update(&transcript)
+            .finalize()
+            .into();
+
+        let local_proof = derive(&master_key, PROOF_PURPOSE, role, &transcript)
+            .finalize().into_bytes();
+        let local_proof = handshake_frame(PROOF_IDENTIFIER, &local_proof);
+        let verify_remote_proof = |inner: &mut dyn Transport| {
+            let remote_proof = receive_handshake_value(inner, PROOF_IDENTIFIER)?;
+            derive(&master_key, PROOF_PURPOSE, role.other(), &transcript)
+                .verify_slice(&remote_proof)
+                .map_err(|_| HandshakeError::AuthenticationFailed)
+        };
+        match role {
+            Role::Host => {
+                inner.send_frame(&local_proof)?;
+                verify_remote_proof(inner.as_mut())?;
+            },
+            Role::Join => {
+                verify_remote_proof(inner.as_mut())?;
+                inner.send_frame(&local_proof)?;
+            },
+        }
+
+        let send_key = derive(&master_key, KEY_PURPOSE, role, &transcript)
+            .finalize().into_bytes();
+        let receive_key = derive(&master_key, KEY_PURPOSE, role.other(), &transcript)
+            .finalize().into_bytes();
+        Ok(EncryptedTransport {
+            inner,
+            send_cipher: ChaCha20Poly1305::new(Key::from_slice(&send_key)),
+            receive_cipher: ChaCha20Poly1305::new(Key::from_slice(&receive_key)),
+            send_counter: 0,
+            receive_counter: 0,
+        })
+    }
+}
+
+/// returns: A generator of the Ristretto255 group whose discrete logarithm is unknown, derived from
+///          the passphrase
+fn generator(passphrase: &str) -> RistrettoPoint {
+    let mut input = GENERATOR_PURPOSE.to_vec();
+    input.extend_from_slice(passphrase.as_bytes());
+    RistrettoPoint::hash_from_bytes::<Sha512>(&input)
+}
+
+fn role_label(role: Role) -> &'static [u8] {
+    match role {
+        Role::Host => b"host",
+        Role::Join => b"join",
+    }
+}
+
+/// returns: A MAC keyed with the master key, bound to the given purpose, role and handshake
+///          transcript
+fn derive(master_key: &[u8; VALUE_SIZE], purpose: &[u8], role: Role,
+          transcript: &[u8]) -> HmacSha256
+{
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(master_key)
+        .expect("HMAC should accept keys of any size");
+    mac.update(purpose);
+    mac.update(role_label(role));
+    mac.update(transcript);
+    mac
+}
+
+fn counter_nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[..8].copy_from_slice(&counter.to_le_bytes());
+    Nonce::clone_from_slice(&nonce)
+}
+
+/// returns: A handshake frame padded to the size of a sealed frame
+fn handshake_frame(identifier: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(SEALED_FRAME_SIZE);
+    frame.extend_from_slice(identifier);
+    frame.extend_from_slice(value);
+    frame.resize(SEALED_FRAME_SIZE, 0);
+    frame
+}
+
+fn receive_handshake_value(transport: &mut dyn Transport,
+                           identifier: &[u8]) -> Result<[u8; VALUE_SIZE], HandshakeError>
+{
+    let frame = wait_for_frame(transport, HANDSHAKE_TIMEOUT)?.ok_or(HandshakeError::Timeout)?;
+    let value = frame.strip_prefix(identifier)
+        .and_then(|rest| rest.get(..VALUE_SIZE))
+        .ok_or(HandshakeError::Malformed)?;
+    Ok(value.try_into().unwrap())
+}
+
+impl Transport for EncryptedTransport {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        let nonce = counter_nonce(self.send_counter);
+        let sealed = self.send_cipher.encrypt(&nonce, frame)
+            .map_err(|_| TransportError::InvalidFrame)?;
+        self.send_counter += 1;
+        self.inner.send_frame(&sealed)
+    }
+
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        let Some(sealed) = self.inner.poll_frame()? else { return Ok(None); };
+        let nonce = counter_nonce(self.receive_counter);
+        // the counter advances even if the frame is rejected, so that later frames still open
+        self.receive_counter += 1;
+        self.receive_cipher.decrypt(&nonce, sealed.as_slice())
+            .map(Some)
+            .map_err(|_| TransportError::Unauthenticated)
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        self.inner.close()
+    }
+
+    fn frame_mode(&self) -> FrameMode {
+        self.inner.frame_mode()
+    }
+
+    fn set_frame_mode(&mut self, frame_mode: FrameMode) {
+        self.inner.set_frame_mode(frame_mode)
+    }
+}
+
+impl Debug for EncryptedTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedTransport")
+            .field("inner", &self.inner)
+            .field("send_counter", &self.send_counter)
+            .field("receive_counter", &self.receive_counter)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::network::transport::MemoryTransport;
+    use super::*;
+
+    #[test]
+    fn players_with_the_same_passphrase_exchange_frames() {
+        let (host, join) = MemoryTransport::pair();
+        let host = thread::spawn(move || {
+            let mut host = EncryptedTransport::handshake(Box::new(host), "secret", Role::Host)
+                .unwrap();
+            wait_for_frame(&mut host, HANDSHAKE_TIMEOUT)
+        });
+        let mut join = EncryptedTransport::handshake(Box::new(join), "secret", Role::Join).unwrap();
+        join.send_frame(b"hello").unwrap();
+        assert_eq!(host.join().unwrap(), Ok(Some(b"hello".to_vec())));
+    }
+
+    #[test]
+    fn players_with_different_passphrases_fail_to_authenticate() {
+        let (host, join) = MemoryTransport::pair();
+        let host = thread::spawn(move || {
+            EncryptedTransport::handshake(Box::new(host), "secret", Role::Host).map(|_| ())
+        });
+        let join = EncryptedTransport::handshake(Box::new(join), "guess", Role::Join).map(|_| ());
+        assert_eq!(join, Err(HandshakeError::AuthenticationFailed));
+        assert!(host.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn invalid_shares_are_rejected() {
+        let (mut fake_host, join) = MemoryTransport::pair();
+        let join = thread::spawn(move || {
+            EncryptedTransport::handshake(Box::new(join), "secret", Role::Join).map(|_| ())
+        });
+        receive_handshake_value(&mut fake_host, SHARE_IDENTIFIER).unwrap();
+        let identity = RistrettoPoint::default().compress().to_bytes();
+        fake_host.send_frame(&handshake_frame(SHARE_IDENTIFIER, &identity)).unwrap();
+
+        assert_eq!(join.join().unwrap(), Err(HandshakeError::Malformed));
+    }
+
+    #[test]
+    fn joining_player_does_not_prove_itself_to_a_fake_host() {
+        let (mut fake_host, join) = MemoryTransport::pair();
+        let join = thread::spawn(move || {
+            EncryptedTransport::handshake(Box::new(join), "secret", Role::Join).map(|_| ())
+        });
+        receive_handshake_value(&mut fake_host, SHARE_IDENTIFIER).unwrap();
+        let fake_share = (generator("guess") * Scalar::from(7u8)).compress().to_bytes();
+        fake_host.send_frame(&handshake_frame(SHARE_IDENTIFIER, &fake_share)).unwrap();
+        fake_host.send_frame(&handshake_frame(PROOF_IDENTIFIER, &[2; VALUE_SIZE])).unwrap();
+
+        assert_eq!(join.join().unwrap(), Err(HandshakeError::AuthenticationFailed));
+        // the joining player gave up without sending its own proof
+        assert_eq!(fake_host.poll_frame(), Err(TransportError::Disconnected));
+    }
+}
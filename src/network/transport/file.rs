@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::network::transport::{FrameMode, Transport, TransportError};
+
+/// [Transport] for correspondence games, where every frame is appended as a line to a game file
+/// shared by both players instead of being sent over a live connection. The file is watched for
+/// lines appended by the other player, so that it can also live in a shared directory.
+#[derive(Debug)]
+pub struct FileTransport {
+    path: PathBuf,
+    /// Number of frames in the file that have already been sent or received
+    frames_seen: usize,
+    /// Modification time and size of the file when it was last read
+    last_read: Option<(SystemTime, u64)>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl FileTransport {
+    /// # Arguments
+    ///
+    /// * `path`: The game file, which must already exist
+    /// * `frames_seen`: Number of frames at the start of the file that should not be received
+    pub fn new(path: &Path, frames_seen: usize) -> FileTransport {
+        FileTransport {
+            path: path.to_owned(),
+            frames_seen,
+            last_read: None,
+            received: VecDeque::new(),
+        }
+    }
+
+    /// Read any frames appended to the file since it was last read
+    fn read_new_frames(&mut self) -> Result<(), TransportError> {
+        let metadata = fs::metadata(&self.path).map_err(|_| TransportError::Disconnected)?;
+        let snapshot = (metadata.modified().map_err(|_| TransportError::Disconnected)?, metadata.len());
+        if self.last_read == Some(snapshot) { return Ok(()); }
+        self.last_read = Some(snapshot);
+
+        let contents = fs::read(&self.path).map_err(|_| TransportError::Disconnected)?;
+        let frames: Vec<&[u8]> = read_game_file_frames(&contents).collect();
+        if frames.len() > self.frames_seen {
+            self.received.extend(frames[self.frames_seen..].iter().map(|frame| frame.to_vec()));
+            self.frames_seen = frames.len();
+        }
+        Ok(())
+    }
+}
+
+/// returns: The frames stored in the contents of a game file, skipping comments and blank lines
+pub fn read_game_file_frames(contents: &[u8]) -> impl Iterator<Item = &[u8]> {
+    contents.split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+}
+
+impl Transport for FileTransport {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        if frame.is_empty() || frame.contains(&b'\n') || frame.starts_with(b"#") {
+            return Err(TransportError::InvalidFrame);
+        }
+        // queue frames appended by the other player first, so that they are not counted as ours
+        self.read_new_frames()?;
+        let mut file = OpenOptions::new().append(true).open(&self.path)
+            .map_err(|_| TransportError::Disconnected)?;
+        file.write_all(&[frame, b"\n"].concat()).map_err(|_| TransportError::Disconnected)?;
+        self.frames_seen += 1;
+        Ok(())
+    }
+
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        if self.received.is_empty() {
+            self.read_new_frames()?;
+        }
+        Ok(self.received.pop_front())
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        Ok(())
+    }
+
+    fn frame_mode(&self) -> FrameMode {
+        // lines have no fixed length, so padding would only make the file harder to read
+        FrameMode::Variable
+    }
+
+    fn set_frame_mode(&mut self, _frame_mode: FrameMode) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_exchange_frames_through_the_file() {
+        let path = std::env::temp_dir().join(format!("chess-file-transport-{}", std::process::id()));
+        fs::write(&path, "# game\nfirst\n").unwrap();
+        let mut white = FileTransport::new(&path, 1);
+        let mut black = FileTransport::new(&path, 1);
+
+        assert_eq!(black.poll_frame(), Ok(None));
+        white.send_frame(b"second").unwrap();
+        assert_eq!(black.poll_frame(), Ok(Some(b"second".to_vec())));
+        assert_eq!(black.poll_frame(), Ok(None));
+        black.send_frame(b"third").unwrap();
+        assert_eq!(white.poll_frame(), Ok(Some(b"third".to_vec())));
+        assert_eq!(white.poll_frame(), Ok(None));
+        assert_eq!(white.send_frame(b"two\nlines"), Err(TransportError::InvalidFrame));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use crate::network::transport::{FrameMode, Transport, TransportError};
+
+/// In-process [Transport], where each frame is passed through a channel to the other half of the
+/// pair. Useful for testing without opening any sockets.
+#[derive(Debug)]
+pub struct MemoryTransport {
+    sender: Option<Sender<Vec<u8>>>,
+    receiver: Receiver<Vec<u8>>,
+    frame_mode: FrameMode,
+}
+
+impl MemoryTransport {
+    /// returns: Two transports connected to each other
+    pub fn pair() -> (MemoryTransport, MemoryTransport) {
+        let (sender_a, receiver_b) = mpsc::channel();
+        let (sender_b, receiver_a) = mpsc::channel();
+        (
+            MemoryTransport { sender: Some(sender_a), receiver: receiver_a, frame_mode: FrameMode::Fixed },
+            MemoryTransport { sender: Some(sender_b), receiver: receiver_b, frame_mode: FrameMode::Fixed },
+        )
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        let sender = self.sender.as_ref().ok_or(TransportError::Disconnected)?;
+        sender.send(frame.to_vec()).map_err(|_| TransportError::Disconnected)
+    }
+
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        match self.receiver.try_recv() {
+            Ok(frame) => Ok(Some(frame)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(TransportError::Disconnected),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        // dropping the sender disconnects the receiving half of the other transport
+        self.sender = None;
+        Ok(())
+    }
+
+    fn frame_mode(&self) -> FrameMode {
+        self.frame_mode
+    }
+
+    fn set_frame_mode(&mut self, frame_mode: FrameMode) {
+        // frames are passed whole, so the mode only affects how messages are encoded
+        self.frame_mode = frame_mode;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_passed_to_the_other_half() {
+        let (mut a, mut b) = MemoryTransport::pair();
+        a.send_frame(b"first").unwrap();
+        a.send_frame(b"second").unwrap();
+        b.send_frame(b"reply").unwrap();
+        assert_eq!(b.poll_frame(), Ok(Some(b"first".to_vec())));
+        assert_eq!(b.poll_frame(), Ok(Some(b"second".to_vec())));
+        assert_eq!(b.poll_frame(), Ok(None));
+        assert_eq!(a.poll_frame(), Ok(Some(b"reply".to_vec())));
+
+        a.close().unwrap();
+        assert_eq!(a.send_frame(b"closed"), Err(TransportError::Disconnected));
+        assert_eq!(b.poll_frame(), Err(TransportError::Disconnected));
+    }
+}
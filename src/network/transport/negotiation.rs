@@ -0,0 +1,268 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::network::transport::{FrameMode, Transport, TransportError, POLL_INTERVAL};
+
+const FRAME_MODE_IDENTIFIER: &[u8] = b"ChessFRMS:";
+/// How long the joining player waits for an answer, before assuming that the host does not support
+/// frame mode negotiation
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+enum Negotiation {
+    /// The host has not received anything from the joining player yet, which may still request a
+    /// frame mode
+    AwaitingRequest {
+        allowed: FrameMode,
+        /// Whether the host has sent any frames, which the joining player reads as a refusal
+        sent_frames: bool,
+    },
+    /// The joining player has requested a frame mode and is waiting for the host to answer
+    AwaitingAnswer {
+        deadline: Instant,
+        /// Frames sent before the answer, which are held back until the frame mode is known
+        queued: Vec<Vec<u8>>,
+    },
+    Done,
+}
+
+/// [Transport] wrapper negotiating the frame mode alongside the first frames of the game, so that
+/// neither player blocks while waiting for the other. Frame mode frames arriving after the
+/// negotiation are dropped, and every other frame is passed through.
+#[derive(Debug)]
+struct NegotiatingTransport {
+    inner: Box<dyn Transport>,
+    /// The size of frames before any frame mode has been negotiated
+    frame_size: usize,
+    negotiation: Negotiation,
+}
+
+impl NegotiatingTransport {
+    /// Switch to the negotiated frame mode and send the frames held back until then. Messages are
+    /// padded to the fixed frame size while negotiating, which peers can decode in either mode.
+    fn finish(&mut self, frame_mode: FrameMode) -> Result<(), TransportError> {
+        self.inner.set_frame_mode(frame_mode);
+        let Negotiation::AwaitingAnswer { queued, .. } =
+            std::mem::replace(&mut self.negotiation, Negotiation::Done)
+        else {
+            return Ok(());
+        };
+        queued.iter().try_for_each(|frame| self.inner.send_frame(frame))
+    }
+
+    /// Give up on an answer from the host once the response timeout has passed
+    fn check_deadline(&mut self) -> Result<(), TransportError> {
+        match self.negotiation {
+            Negotiation::AwaitingAnswer { deadline, .. } if Instant::now() >= deadline => {
+                self.finish(FrameMode::Fixed)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Answer a frame mode request from the joining player. Variable-length frames are only used
+    /// if both players want them, and the host has not sent any fixed-size frames yet.
+    fn answer(&mut self, requested: FrameMode) -> Result<(), TransportError> {
+        let Negotiation::AwaitingRequest { allowed, sent_frames } = self.negotiation else {
+            return Ok(());
+        };
+        self.negotiation = Negotiation::Done;
+        let frame_mode = match (requested, allowed, sent_frames) {
+            (FrameMode::Variable, FrameMode::Variable, false) => FrameMode::Variable,
+            _ => FrameMode::Fixed,
+        };
+        self.inner.send_frame(&frame_mode_frame(frame_mode, self.frame_size))?;
+        self.inner.set_frame_mode(frame_mode);
+        Ok(())
+    }
+}
+
+impl Transport for NegotiatingTransport {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        self.check_deadline()?;
+        match &mut self.negotiation {
+            Negotiation::AwaitingRequest { sent_frames, .. } => *sent_frames = true,
+            Negotiation::AwaitingAnswer { queued, .. } => {
+                queued.push(frame.to_vec());
+                return Ok(());
+            },
+            Negotiation::Done => {},
+        }
+        self.inner.send_frame(frame)
+    }
+
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        self.check_deadline()?;
+        while let Some(frame) = self.inner.poll_frame()? {
+            match (parse_frame_mode_frame(&frame), &self.negotiation) {
+                (Some(requested), Negotiation::AwaitingRequest { .. }) => self.answer(requested)?,
+                (Some(answer), Negotiation::AwaitingAnswer { .. }) => self.finish(answer)?,
+                (Some(_), Negotiation::Done) => {},
+                // a peer that sends other frames first is not negotiating
+                (None, Negotiation::AwaitingRequest { .. }) => {
+                    self.negotiation = Negotiation::Done;
+                    return Ok(Some(frame));
+                },
+                (None, Negotiation::AwaitingAnswer { .. }) => {
+                    self.finish(FrameMode::Fixed)?;
+                    return Ok(Some(frame));
+                },
+                (None, Negotiation::Done) => return Ok(Some(frame)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        // frames held back until the answer, such as a quit message, must still be sent
+        while let Negotiation::AwaitingAnswer { queued, .. } = &self.negotiation {
+            if queued.is_empty() || self.poll_frame().is_err() { break; }
+            thread::sleep(POLL_INTERVAL);
+        }
+        self.inner.close()
+    }
+
+    fn frame_mode(&self) -> FrameMode {
+        self.inner.frame_mode()
+    }
+
+    fn set_frame_mode(&mut self, frame_mode: FrameMode) {
+        self.inner.set_frame_mode(frame_mode)
+    }
+}
+
+/// returns: A frame announcing the frame mode, padded with `'0'` to the current fixed frame size
+fn frame_mode_frame(frame_mode: FrameMode, frame_size: usize) -> Vec<u8> {
+    let name: &[u8] = match frame_mode {
+        FrameMode::Fixed => b"fixed",
+        FrameMode::Variable => b"variable",
+    };
+    let mut frame = [FRAME_MODE_IDENTIFIER, name, b":"].concat();
+    frame.resize(frame_size, b'0');
+    frame
+}
+
+/// returns: The frame mode announced by the frame, or `None` if it is some other frame
+fn parse_frame_mode_frame(frame: &[u8]) -> Option<FrameMode> {
+    let rest = frame.strip_prefix(FRAME_MODE_IDENTIFIER)?;
+    let end = rest.iter().position(|b| *b == b':')?;
+    if !rest[end + 1..].iter().all(|b| *b == b'0') { return None; }
+    match &rest[..end] {
+        b"fixed" => Some(FrameMode::Fixed),
+        b"variable" => Some(FrameMode::Variable),
+        _ => None,
+    }
+}
+
+/// Wrap the host's transport to answer a frame mode request from the joining player, if its first
+/// frame is one
+///
+/// # Arguments
+///
+/// * `allowed`: The frame mode the host wants to use
+/// * `frame_size`: The size of frames before any frame mode has been negotiated
+pub fn negotiate_as_host(transport: Box<dyn Transport>, allowed: FrameMode,
+                         frame_size: usize) -> Box<dyn Transport>
+{
+    Box::new(NegotiatingTransport {
+        inner: transport,
+        frame_size,
+        negotiation: Negotiation::AwaitingRequest { allowed, sent_frames: false },
+    })
+}
+
+/// Request a frame mode from the host, falling back to fixed-size frames if the host declines,
+/// sends something else first or does not answer in time. Nothing is sent when requesting
+/// fixed-size frames, which is what every host supports.
+///
+/// # Arguments
+///
+/// * `requested`: The frame mode the joining player wants to use
+/// * `frame_size`: The size of frames before any frame mode has been negotiated
+pub fn negotiate_as_join(mut transport: Box<dyn Transport>, requested: FrameMode,
+                         frame_size: usize) -> Result<Box<dyn Transport>, TransportError>
+{
+    if requested == FrameMode::Fixed { return Ok(transport); }
+    transport.send_frame(&frame_mode_frame(requested, frame_size))?;
+    Ok(Box::new(NegotiatingTransport {
+        inner: transport,
+        frame_size,
+        negotiation: Negotiation::AwaitingAnswer {
+            deadline: Instant::now() + RESPONSE_TIMEOUT,
+            queued: Vec::new(),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::chess_tp::{Message, BUFFER_SIZE};
+    use crate::network::transport::MemoryTransport;
+    use super::*;
+
+    fn pair(host_mode: FrameMode,
+            join_mode: FrameMode) -> (Box<dyn Transport>, Box<dyn Transport>)
+    {
+        let (host, join) = MemoryTransport::pair();
+        let host = negotiate_as_host(Box::new(host), host_mode, BUFFER_SIZE);
+        (host, negotiate_as_join(Box::new(join), join_mode, BUFFER_SIZE).unwrap())
+    }
+
+    fn quit_frame() -> Vec<u8> {
+        Message::ChessQuit { payload: String::new() }.encode().unwrap().to_vec()
+    }
+
+    fn negotiate(host_mode: FrameMode, join_mode: FrameMode) -> (FrameMode, FrameMode) {
+        let (mut host, mut join) = pair(host_mode, join_mode);
+        assert_eq!(host.poll_frame(), Ok(None));
+        assert_eq!(join.poll_frame(), Ok(None));
+        (host.frame_mode(), join.frame_mode())
+    }
+
+    #[test]
+    fn variable_frames_need_both_players() {
+        assert_eq!(negotiate(FrameMode::Variable, FrameMode::Variable),
+                   (FrameMode::Variable, FrameMode::Variable));
+        assert_eq!(negotiate(FrameMode::Fixed, FrameMode::Variable), (FrameMode::Fixed, FrameMode::Fixed));
+        assert_eq!(negotiate(FrameMode::Variable, FrameMode::Fixed), (FrameMode::Fixed, FrameMode::Fixed));
+    }
+
+    #[test]
+    fn host_keeps_frames_from_peers_that_do_not_negotiate() {
+        let (host, mut join) = MemoryTransport::pair();
+        join.send_frame(&quit_frame()).unwrap();
+        let mut host = negotiate_as_host(Box::new(host), FrameMode::Variable, BUFFER_SIZE);
+        assert_eq!(host.poll_frame(), Ok(Some(quit_frame())));
+        assert_eq!(host.frame_mode(), FrameMode::Fixed);
+    }
+
+    #[test]
+    fn joining_player_holds_back_frames_until_answered() {
+        let (mut host, mut join) = pair(FrameMode::Variable, FrameMode::Variable);
+        join.send_frame(&quit_frame()).unwrap();
+        assert_eq!(host.poll_frame(), Ok(None));
+        assert_eq!(join.poll_frame(), Ok(None));
+        assert_eq!(host.poll_frame(), Ok(Some(quit_frame())));
+        assert_eq!(host.frame_mode(), FrameMode::Variable);
+        assert_eq!(join.frame_mode(), FrameMode::Variable);
+    }
+
+    #[test]
+    fn frames_sent_by_the_host_first_decline_the_request() {
+        let (mut host, mut join) = pair(FrameMode::Variable, FrameMode::Variable);
+        host.send_frame(&quit_frame()).unwrap();
+        assert_eq!(host.poll_frame(), Ok(None));
+        assert_eq!(join.poll_frame(), Ok(Some(quit_frame())));
+        // the late answer is dropped
+        assert_eq!(join.poll_frame(), Ok(None));
+        assert_eq!(host.frame_mode(), FrameMode::Fixed);
+        assert_eq!(join.frame_mode(), FrameMode::Fixed);
+    }
+
+    #[test]
+    fn frame_mode_frames_are_padded() {
+        let frame = frame_mode_frame(FrameMode::Variable, BUFFER_SIZE);
+        assert_eq!(frame.len(), BUFFER_SIZE);
+        assert_eq!(parse_frame_mode_frame(&frame), Some(FrameMode::Variable));
+        assert!(Message::decode(&frame).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Debug;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use crate::network::chess_tp;
+use crate::network::transport::{FrameMode, Transport, TransportError, POLL_INTERVAL};
+
+const READ_CHUNK_SIZE: usize = 512;
+/// How long closing the transport waits for queued bytes to be written
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+/// Size of the big-endian length header preceding each frame in [FrameMode::Variable]
+const LENGTH_HEADER_SIZE: usize = 2;
+
+/// A byte stream that frames can be sent over
+pub trait Stream: Read + Write + Debug {
+    /// Shut down both halves of the stream
+    fn shutdown(&mut self) -> io::Result<()>;
+}
+
+impl Stream for TcpStream {
+    fn shutdown(&mut self) -> io::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn shutdown(&mut self) -> io::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Both)
+    }
+}
+
+/// [Transport] over a non-blocking byte stream, splitting the stream into fixed-size frames, or
+/// into frames preceded by a length header
+#[derive(Debug)]
+pub struct StreamTransport<S: Stream> {
+    stream: S,
+    frame_size: usize,
+    frame_mode: FrameMode,
+    read_buffer: Vec<u8>,
+    /// Bytes of sent frames that the stream has not accepted yet, because it would have blocked
+    write_buffer: Vec<u8>,
+}
+
+impl<S: Stream> StreamTransport<S> {
+    /// Create a transport sending frames of [chess_tp::BUFFER_SIZE] bytes
+    pub fn new(stream: S) -> StreamTransport<S> {
+        StreamTransport::with_frame_size(stream, chess_tp::BUFFER_SIZE)
+    }
+
+    /// # Arguments
+    ///
+    /// * `frame_size`: The size of every frame in [FrameMode::Fixed]
+    pub fn with_frame_size(stream: S, frame_size: usize) -> StreamTransport<S> {
+        StreamTransport {
+            stream,
+            frame_size,
+            frame_mode: FrameMode::Fixed,
+            read_buffer: Vec::with_capacity(frame_size),
+            write_buffer: Vec::new(),
+        }
+    }
+
+    /// Write as much of the write buffer as the stream accepts without blocking
+    fn flush_write_buffer(&mut self) -> Result<(), TransportError> {
+        while !self.write_buffer.is_empty() {
+            match self.stream.write(&self.write_buffer) {
+                Ok(0) => return Err(TransportError::Disconnected),
+                Ok(len) => { self.write_buffer.drain(..len); },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return Err(TransportError::Disconnected),
+            }
+        }
+        Ok(())
+    }
+
+    /// returns: The next complete frame in the read buffer, if any
+    fn take_frame(&mut self) -> Option<Vec<u8>> {
+        let (start, len) = match self.frame_mode {
+            FrameMode::Fixed => (0, self.frame_size),
+            FrameMode::Variable => {
+                let header = self.read_buffer.get(..LENGTH_HEADER_SIZE)?;
+                (LENGTH_HEADER_SIZE, u16::from_be_bytes([header[0], header[1]]) as usize)
+            },
+        };
+        if self.read_buffer.len() < start + len { return None; }
+        let rest = self.read_buffer.split_off(start + len);
+        let frame = std::mem::replace(&mut self.read_buffer, rest);
+        Some(frame[start..].to_vec())
+    }
+}
+
+impl<S: Stream> Transport for StreamTransport<S> {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        let frame = match self.frame_mode {
+            FrameMode::Fixed => {
+                if frame.len() != self.frame_size { return Err(TransportError::InvalidFrame); }
+                frame.to_vec()
+            },
+            FrameMode::Variable => {
+                let len = u16::try_from(frame.len()).map_err(|_| TransportError::InvalidFrame)?;
+                [&len.to_be_bytes(), frame].concat()
+            },
+        };
+        // a frame that is only partly written is finished on later sends and polls, so that the
+        // stream never contains a truncated frame
+        self.write_buffer.extend_from_slice(&frame);
+        self.flush_write_buffer()
+    }
+
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        self.flush_write_buffer()?;
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        loop {
+            if let Some(frame) = self.take_frame() {
+                return Ok(Some(frame));
+            }
+            match self.stream.read(&mut chunk) {
+                // the other end has closed the stream
+                Ok(0) => return Err(TransportError::Disconnected),
+                Ok(len) => self.read_buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return Err(TransportError::Disconnected),
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        let start = Instant::now();
+        while !self.write_buffer.is_empty() && start.elapsed() < CLOSE_FLUSH_TIMEOUT {
+            self.flush_write_buffer()?;
+            thread::sleep(POLL_INTERVAL);
+        }
+        self.stream.shutdown().map_err(|_| TransportError::Disconnected)
+    }
+
+    fn frame_mode(&self) -> FrameMode {
+        self.frame_mode
+    }
+
+    fn set_frame_mode(&mut self, frame_mode: FrameMode) {
+        self.frame_mode = frame_mode;
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::net::TcpListener;
+    use crate::network::transport::wait_for_frame;
+    use super::*;
+
+    fn pair() -> (StreamTransport<UnixStream>, StreamTransport<UnixStream>) {
+        let (a, b) = UnixStream::pair().unwrap();
+        a.set_nonblocking(true).unwrap();
+        b.set_nonblocking(true).unwrap();
+        (StreamTransport::new(a), StreamTransport::new(b))
+    }
+
+    #[test]
+    fn fixed_frames_must_have_the_frame_size() {
+        let (mut a, mut b) = pair();
+        assert_eq!(a.send_frame(b"short"), Err(TransportError::InvalidFrame));
+        a.send_frame(&[b'x'; chess_tp::BUFFER_SIZE]).unwrap();
+        assert_eq!(b.poll_frame(), Ok(Some(vec![b'x'; chess_tp::BUFFER_SIZE])));
+    }
+
+    #[test]
+    fn variable_frames_are_split_by_length_header() {
+        let (mut a, mut b) = pair();
+        a.set_frame_mode(FrameMode::Variable);
+        b.set_frame_mode(FrameMode::Variable);
+        let long_frame = vec![b'y'; 1000];
+        for frame in [&b"short"[..], b"", &long_frame] {
+            a.send_frame(frame).unwrap();
+        }
+        assert_eq!(b.poll_frame(), Ok(Some(b"short".to_vec())));
+        assert_eq!(b.poll_frame(), Ok(Some(Vec::new())));
+        assert_eq!(b.poll_frame(), Ok(Some(long_frame)));
+        assert_eq!(b.poll_frame(), Ok(None));
+        assert_eq!(a.send_frame(&vec![0; 70000]), Err(TransportError::InvalidFrame));
+    }
+
+    #[test]
+    fn exchanges_frames_over_tcp_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client.set_nonblocking(true).unwrap();
+        server.set_nonblocking(true).unwrap();
+        let (mut client, mut server) = (StreamTransport::new(client), StreamTransport::new(server));
+
+        client.send_frame(&[b'c'; chess_tp::BUFFER_SIZE]).unwrap();
+        server.send_frame(&[b's'; chess_tp::BUFFER_SIZE]).unwrap();
+        let timeout = Duration::from_secs(5);
+        let frame = |byte| Ok(Some(vec![byte; chess_tp::BUFFER_SIZE]));
+        assert_eq!(wait_for_frame(&mut server, timeout), frame(b'c'));
+        assert_eq!(wait_for_frame(&mut client, timeout), frame(b's'));
+
+        client.close().unwrap();
+        assert_eq!(wait_for_frame(&mut server, timeout), Err(TransportError::Disconnected));
+    }
+
+    #[test]
+    fn frames_are_queued_while_the_stream_is_full() {
+        let (mut a, mut b) = pair();
+        a.set_frame_mode(FrameMode::Variable);
+        b.set_frame_mode(FrameMode::Variable);
+        // far more than the socket buffer holds, so that writing would block part way
+        let frames: Vec<Vec<u8>> = (0..100_u8).map(|index| vec![index; 60000]).collect();
+        for frame in &frames {
+            a.send_frame(frame).unwrap();
+        }
+        assert!(!a.write_buffer.is_empty());
+
+        let mut received = Vec::new();
+        while received.len() < frames.len() {
+            // polling also writes the queued bytes that now fit
+            assert_eq!(a.poll_frame(), Ok(None));
+            while let Some(frame) = b.poll_frame().unwrap() {
+                received.push(frame);
+            }
+        }
+        assert_eq!(received, frames);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use tungstenite::{Message, WebSocket};
+use crate::network::transport::{FrameMode, Transport, TransportError};
+
+/// [Transport] carrying each frame as a single binary WebSocket message
+pub struct WebSocketTransport<S: Read + Write> {
+    socket: WebSocket<S>,
+    frame_mode: FrameMode,
+}
+
+impl<S: Read + Write> WebSocketTransport<S> {
+    /// Wrap a WebSocket whose opening handshake has already been completed
+    pub fn new(socket: WebSocket<S>) -> WebSocketTransport<S> {
+        WebSocketTransport { socket, frame_mode: FrameMode::Fixed }
+    }
+
+    /// Perform the server side of the opening handshake over `stream`
+    pub fn accept(stream: S) -> io::Result<WebSocketTransport<S>> {
+        let socket = tungstenite::accept(stream)
+            .map_err(|e| io::Error::new(ErrorKind::ConnectionRefused, e.to_string()))?;
+        Ok(WebSocketTransport::new(socket))
+    }
+
+    /// Perform the client side of the opening handshake over `stream`
+    ///
+    /// # Arguments
+    ///
+    /// * `url`: The `ws://` URL of the server, sent as part of the handshake request
+    pub fn connect(url: &str, stream: S) -> io::Result<WebSocketTransport<S>> {
+        let (socket, _) = tungstenite::client(url, stream)
+            .map_err(|e| io::Error::new(ErrorKind::ConnectionRefused, e.to_string()))?;
+        Ok(WebSocketTransport::new(socket))
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.socket.get_ref()
+    }
+}
+
+/// returns: Whether the error only signals that a non-blocking operation could not complete yet
+fn is_would_block(error: &tungstenite::Error) -> bool {
+    matches!(error, tungstenite::Error::Io(e) if e.kind() == ErrorKind::WouldBlock)
+}
+
+impl<S: Read + Write + Debug> Transport for WebSocketTransport<S> {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        match self.socket.send(Message::binary(frame.to_vec())) {
+            Ok(()) => Ok(()),
+            // the message has been queued, and is flushed by subsequent reads
+            Err(e) if is_would_block(&e) => Ok(()),
+            Err(_) => Err(TransportError::Disconnected),
+        }
+    }
+
+    fn poll_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        loop {
+            match self.socket.read() {
+                Ok(Message::Binary(data)) => return Ok(Some(data.to_vec())),
+                Ok(Message::Close(_)) => return Err(TransportError::Disconnected),
+                // pings are answered automatically, other messages are not part of the protocol
+                Ok(_) => continue,
+                Err(e) if is_would_block(&e) => return Ok(None),
+                Err(_) => return Err(TransportError::Disconnected),
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), TransportError> {
+        match self.socket.close(None) {
+            Ok(()) => Ok(()),
+            Err(e) if is_would_block(&e) => Ok(()),
+            Err(_) => Err(TransportError::Disconnected),
+        }
+    }
+
+    fn frame_mode(&self) -> FrameMode {
+        self.frame_mode
+    }
+
+    fn set_frame_mode(&mut self, frame_mode: FrameMode) {
+        // each frame is its own WebSocket message, so the mode only affects how messages are encoded
+        self.frame_mode = frame_mode;
+    }
+}
+
+impl<S: Read + Write + Debug> Debug for WebSocketTransport<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocketTransport").field("stream", self.socket.get_ref()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+    use rsoderh_chess::{Board, Color, HalfMoveRequest, Position};
+    use crate::network::chess_tp;
+    use crate::network::chess_tp::GameStateType;
+    use crate::network::transport::wait_for_frame;
+    use super::*;
+
+    /// # Arguments
+    ///
+    /// * `player`: The player making the move, which is not part of the encoded message
+    fn move_message(player: Option<Color>) -> chess_tp::Message {
+        chess_tp::Message::ChessMove {
+            player,
+            chess_move: HalfMoveRequest::Standard {
+                source: Position::new(4, 1).unwrap(),
+                dest: Position::new(4, 3).unwrap(),
+            },
+            new_game_state: GameStateType::Normal,
+            new_board: Board::new_empty(),
+        }
+    }
+
+    fn quit_message() -> chess_tp::Message {
+        chess_tp::Message::ChessQuit { payload: "bye".to_owned() }
+    }
+
+    #[test]
+    fn host_and_join_exchange_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let transport = WebSocketTransport::accept(stream).unwrap();
+            transport.get_ref().set_nonblocking(true).unwrap();
+            transport
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        let mut join = WebSocketTransport::connect(&format!("ws://{address}/"), stream).unwrap();
+        join.get_ref().set_nonblocking(true).unwrap();
+        let mut host = host.join().unwrap();
+
+        let timeout = Duration::from_secs(5);
+        join.send_frame(&move_message(Some(Color::White)).encode().unwrap()).unwrap();
+        let frame = wait_for_frame(&mut host, timeout).unwrap().unwrap();
+        assert_eq!(chess_tp::Message::decode(&frame), Ok(move_message(None)));
+
+        host.send_frame(&quit_message().encode().unwrap()).unwrap();
+        let frame = wait_for_frame(&mut join, timeout).unwrap().unwrap();
+        assert_eq!(chess_tp::Message::decode(&frame), Ok(quit_message()));
+
+        join.close().unwrap();
+        assert_eq!(wait_for_frame(&mut host, timeout), Err(TransportError::Disconnected));
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use ggez::audio::{SoundData, SoundSource, Source};
 use ggez::context::Context;
 use ggez::GameResult;
 use ggez::graphics::Image;
@@ -17,10 +18,17 @@ pub const BLACK_QUEEN_IMAGE: &str = "/pieces/Chess_qdt45.png";
 pub const WHITE_KING_IMAGE: &str = "/pieces/Chess_klt45.png";
 pub const BLACK_KING_IMAGE: &str = "/pieces/Chess_kdt45.png";
 
+pub const MOVE_SOUND: &str = "/sounds/move.wav";
+pub const CAPTURE_SOUND: &str = "/sounds/capture.wav";
+pub const CASTLE_SOUND: &str = "/sounds/castle.wav";
+pub const CHECK_SOUND: &str = "/sounds/check.wav";
+pub const GAME_OVER_SOUND: &str = "/sounds/game_over.wav";
+
 /// Collection of various resources required for the application
 #[derive(Clone, Debug)]
 pub struct Resources {
     pub images: ImageResources,
+    pub sounds: SoundResources,
 }
 
 /// Contains a resource with its associated file path
@@ -69,6 +77,45 @@ impl ImageResources {
     }
 }
 
+/// Events that a sound effect is played for
+#[derive(Copy, Clone, Debug)]
+pub enum SoundEffect {
+    Move,
+    Capture,
+    Castle,
+    Check,
+    GameOver,
+}
+
+/// Collection of all the sound effects required for the application
+#[derive(Clone, Debug)]
+pub struct SoundResources {
+    pub move_sound: ResourceEntry<SoundData>,
+    pub capture: ResourceEntry<SoundData>,
+    pub castle: ResourceEntry<SoundData>,
+    pub check: ResourceEntry<SoundData>,
+    pub game_over: ResourceEntry<SoundData>,
+}
+
+impl SoundResources {
+    /// returns: Which sound resource corresponds to the given sound effect
+    pub fn get_effect(&self, effect: SoundEffect) -> &ResourceEntry<SoundData> {
+        match effect {
+            SoundEffect::Move => &self.move_sound,
+            SoundEffect::Capture => &self.capture,
+            SoundEffect::Castle => &self.castle,
+            SoundEffect::Check => &self.check,
+            SoundEffect::GameOver => &self.game_over,
+        }
+    }
+
+    /// Play a sound effect without waiting for it to finish
+    pub fn play(&self, ctx: &mut Context, effect: SoundEffect) -> GameResult {
+        let data = self.get_effect(effect).resource.clone();
+        Source::from_data(ctx, data)?.play_detached(ctx)
+    }
+}
+
 /// Create an image resource given its file path
 fn create_image_resource(ctx: &mut Context,
                          path: &'static str) -> GameResult<ResourceEntry<Image>>
@@ -79,6 +126,16 @@ fn create_image_resource(ctx: &mut Context,
     })
 }
 
+/// Create a sound resource given its file path
+fn create_sound_resource(ctx: &mut Context,
+                         path: &'static str) -> GameResult<ResourceEntry<SoundData>>
+{
+    Ok(ResourceEntry {
+        path,
+        resource: SoundData::new(ctx, path)?,
+    })
+}
+
 impl Resources {
     /// Instantiate a `Resources` object, loading all required resources
     pub fn new(ctx: &mut Context) -> GameResult<Resources> {
@@ -96,7 +153,14 @@ impl Resources {
                 black_queen: create_image_resource(ctx, BLACK_QUEEN_IMAGE)?,
                 white_king: create_image_resource(ctx, WHITE_KING_IMAGE)?,
                 black_king: create_image_resource(ctx, BLACK_KING_IMAGE)?,
-            }
+            },
+            sounds: SoundResources {
+                move_sound: create_sound_resource(ctx, MOVE_SOUND)?,
+                capture: create_sound_resource(ctx, CAPTURE_SOUND)?,
+                castle: create_sound_resource(ctx, CASTLE_SOUND)?,
+                check: create_sound_resource(ctx, CHECK_SOUND)?,
+                game_over: create_sound_resource(ctx, GAME_OVER_SOUND)?,
+            },
         })
     }
 }